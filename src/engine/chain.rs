@@ -0,0 +1,119 @@
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use winit::event::{DeviceEvent, WindowEvent};
+
+use crate::engine::{signal::QueryError, GpuCache, Items, RenderTarget, SystemPipeline};
+
+/// Two system pipelines run together as one.
+///
+/// Each hook is called on the first pipeline, then on the second, with the same
+/// [`Items`]. Their arguments are combined into a tuple, and their signals and
+/// queries into [`Either`]. Longer chains are made by nesting, e.g.
+/// `Chain<A, Chain<B, C>>`.
+///
+/// Both share the render target and the GPU context, so usually only one of
+/// them renders to the target, e.g. with a stats collector next to it.
+pub struct Chain<A, B>(pub A, pub B);
+
+impl<A: SystemPipeline, B: SystemPipeline> SystemPipeline for Chain<A, B> {
+    type Args = (A::Args, B::Args);
+    type InSignal = Either<A::InSignal, B::InSignal>;
+    type OutSignal = Either<A::OutSignal, B::OutSignal>;
+    type Query = Either<A::Query, B::Query>;
+    type Error = ChainError<A::Error, B::Error>;
+
+    async fn init(
+        target: RenderTarget,
+        gpu_cache: GpuCache,
+        (first_args, second_args): Self::Args,
+    ) -> Result<Self, Self::Error> {
+        let first = A::init(target.clone(), gpu_cache.clone(), first_args)
+            .await
+            .map_err(ChainError::First)?;
+        let second = B::init(target, gpu_cache, second_args)
+            .await
+            .map_err(ChainError::Second)?;
+
+        Ok(Self(first, second))
+    }
+
+    fn device_event(&mut self, items: &mut Items<Self::OutSignal>, event: &DeviceEvent) {
+        items.with_mapped_tx(Either::First, |items| self.0.device_event(items, event));
+        items.with_mapped_tx(Either::Second, |items| self.1.device_event(items, event));
+    }
+
+    fn window_event(&mut self, items: &mut Items<Self::OutSignal>, event: &WindowEvent) {
+        items.with_mapped_tx(Either::First, |items| self.0.window_event(items, event));
+        items.with_mapped_tx(Either::Second, |items| self.1.window_event(items, event));
+    }
+
+    fn update(&mut self, items: &mut Items<Self::OutSignal>) {
+        items.with_mapped_tx(Either::First, |items| self.0.update(items));
+        items.with_mapped_tx(Either::Second, |items| self.1.update(items));
+    }
+
+    fn fixed_update(&mut self, items: &mut Items<Self::OutSignal>) {
+        items.with_mapped_tx(Either::First, |items| self.0.fixed_update(items));
+        items.with_mapped_tx(Either::Second, |items| self.1.fixed_update(items));
+    }
+
+    fn pause(&mut self, items: &mut Items<Self::OutSignal>) {
+        items.with_mapped_tx(Either::First, |items| self.0.pause(items));
+        items.with_mapped_tx(Either::Second, |items| self.1.pause(items));
+    }
+
+    fn resume(&mut self, items: &mut Items<Self::OutSignal>) {
+        items.with_mapped_tx(Either::First, |items| self.0.resume(items));
+        items.with_mapped_tx(Either::Second, |items| self.1.resume(items));
+    }
+
+    /// The format of the first pipeline rendering to a surface.
+    fn surface_format(&self) -> Option<wgpu::TextureFormat> {
+        self.0.surface_format().or_else(|| self.1.surface_format())
+    }
+
+    /// Route the signal to the pipeline it is for.
+    fn in_signal(&mut self, items: &mut Items<Self::OutSignal>, signal: Self::InSignal) {
+        match signal {
+            Either::First(signal) => {
+                items.with_mapped_tx(Either::First, |items| self.0.in_signal(items, signal))
+            }
+            Either::Second(signal) => {
+                items.with_mapped_tx(Either::Second, |items| self.1.in_signal(items, signal))
+            }
+        }
+    }
+
+    /// Route the query to the pipeline it is for.
+    fn query(
+        &mut self,
+        items: &mut Items<Self::OutSignal>,
+        query: Self::Query,
+    ) -> Result<Self::OutSignal, QueryError> {
+        match query {
+            Either::First(query) => items
+                .with_mapped_tx(Either::First, |items| self.0.query(items, query))
+                .map(Either::First),
+            Either::Second(query) => items
+                .with_mapped_tx(Either::Second, |items| self.1.query(items, query))
+                .map(Either::Second),
+        }
+    }
+}
+
+/// Signal or query of one of the pipelines of a [`Chain`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Either<A, B> {
+    First(A),
+    Second(B),
+}
+
+/// Error of one of the pipelines of a [`Chain`] when initializing.
+#[derive(Debug, Error)]
+pub enum ChainError<A, B> {
+    #[error("First system pipeline error: {0}")]
+    First(A),
+
+    #[error("Second system pipeline error: {0}")]
+    Second(B),
+}
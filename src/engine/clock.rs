@@ -0,0 +1,102 @@
+use std::{cell::Cell, fmt::Debug, rc::Rc, time::Duration};
+
+/// Source of time of the engine timers, e.g. the signal queue expiry, and of
+/// [`crate::systems::handlers::Time`].
+pub trait Clock: Debug {
+    /// The time since a fixed origin, which never goes backwards.
+    fn now(&self) -> Duration;
+}
+
+cfg_if::cfg_if! {
+    if #[cfg(target_arch = "wasm32")] {
+        /// The monotonic clock of the platform.
+        pub type MonotonicClock = PerformanceClock;
+
+        /// Clock using `performance.now()`.
+        #[derive(Debug, Clone)]
+        pub struct PerformanceClock {
+            performance: web_sys::Performance,
+        }
+
+        impl PerformanceClock {
+            pub fn new() -> Self {
+                Self {
+                    performance: web_sys::window()
+                        .and_then(|window| window.performance())
+                        .expect("performance"),
+                }
+            }
+        }
+
+        impl Default for PerformanceClock {
+            fn default() -> Self {
+                Self::new()
+            }
+        }
+
+        impl Clock for PerformanceClock {
+            fn now(&self) -> Duration {
+                Duration::from_secs_f64(self.performance.now().max(0.0) / 1000.0)
+            }
+        }
+    } else {
+        /// The monotonic clock of the platform.
+        pub type MonotonicClock = InstantClock;
+
+        /// Clock using [`std::time::Instant`].
+        #[derive(Debug, Clone)]
+        pub struct InstantClock {
+            origin: std::time::Instant,
+        }
+
+        impl InstantClock {
+            pub fn new() -> Self {
+                Self {
+                    origin: std::time::Instant::now(),
+                }
+            }
+        }
+
+        impl Default for InstantClock {
+            fn default() -> Self {
+                Self::new()
+            }
+        }
+
+        impl Clock for InstantClock {
+            fn now(&self) -> Duration {
+                self.origin.elapsed()
+            }
+        }
+    }
+}
+
+/// Clock which only moves when it is advanced, e.g. in tests.
+///
+/// Clones share the same time, so a clone can be given to a timer and the
+/// original advanced.
+#[derive(Debug, Clone, Default)]
+pub struct ManualClock {
+    now: Rc<Cell<Duration>>,
+}
+
+impl ManualClock {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn advance(&self, duration: Duration) {
+        self.now.set(self.now.get() + duration);
+    }
+
+    /// Set the time, it is not set backwards.
+    pub fn set(&self, now: Duration) {
+        self.now.set(self.now.get().max(now));
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Duration {
+        self.now.get()
+    }
+}
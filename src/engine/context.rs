@@ -0,0 +1,128 @@
+use std::{cell::Cell, sync::Arc, time::Duration};
+
+use winit::{
+    dpi::PhysicalSize,
+    event_loop::{ActiveEventLoop, ControlFlow},
+    window::WindowAttributes,
+};
+
+use crate::engine::{Error, RenderTarget};
+
+/// The interaction of the [`crate::engine::Engine`] with the event loop.
+///
+/// This is implemented by [`ActiveEventLoop`], and by [`MockEventLoop`] so
+/// that the engine can be driven without winit.
+pub trait EventLoopContext {
+    /// Create the render target with the window attributes.
+    fn create_target(&self, window_attributes: WindowAttributes) -> Result<RenderTarget, Error>;
+
+    /// Exit the event loop.
+    fn exit(&self);
+
+    /// Request a redraw of the render target.
+    fn request_redraw(&self, target: &RenderTarget);
+
+    /// Wake the engine with [`crate::engine::Engine::on_frame_timeout`] after
+    /// the timeout, or only on events if there is none.
+    fn set_frame_timeout(&self, timeout: Option<Duration>);
+}
+
+impl EventLoopContext for ActiveEventLoop {
+    fn create_target(&self, window_attributes: WindowAttributes) -> Result<RenderTarget, Error> {
+        let window = Arc::new(self.create_window(window_attributes)?);
+
+        #[cfg(target_arch = "wasm32")]
+        {
+            use winit::platform::web::WindowExtWebSys;
+
+            window.set_prevent_default(true);
+        }
+
+        Ok(RenderTarget::Window(window))
+    }
+
+    fn exit(&self) {
+        ActiveEventLoop::exit(self);
+    }
+
+    fn request_redraw(&self, target: &RenderTarget) {
+        target.request_redraw();
+    }
+
+    fn set_frame_timeout(&self, timeout: Option<Duration>) {
+        self.set_control_flow(match timeout {
+            Some(timeout) => ControlFlow::wait_duration(timeout),
+            None => ControlFlow::Wait,
+        });
+    }
+}
+
+/// Mock event loop creating headless render targets.
+///
+/// It records the calls made by the engine, so its lifecycle can be checked
+/// without a window or a display.
+#[derive(Debug)]
+pub struct MockEventLoop {
+    size: PhysicalSize<u32>,
+    created_targets: Cell<usize>,
+    redraw_requests: Cell<usize>,
+    frame_timeout: Cell<Option<Duration>>,
+    exited: Cell<bool>,
+}
+
+impl MockEventLoop {
+    pub fn new(size: PhysicalSize<u32>) -> Self {
+        Self {
+            size,
+            created_targets: Cell::new(0),
+            redraw_requests: Cell::new(0),
+            frame_timeout: Cell::new(None),
+            exited: Cell::new(false),
+        }
+    }
+
+    /// Number of render targets created.
+    pub fn created_targets(&self) -> usize {
+        self.created_targets.get()
+    }
+
+    /// Number of redraws requested.
+    pub fn redraw_requests(&self) -> usize {
+        self.redraw_requests.get()
+    }
+
+    /// The frame timeout last set, if any.
+    pub fn frame_timeout(&self) -> Option<Duration> {
+        self.frame_timeout.get()
+    }
+
+    /// Whether the event loop has been exited.
+    pub fn exited(&self) -> bool {
+        self.exited.get()
+    }
+}
+
+impl Default for MockEventLoop {
+    fn default() -> Self {
+        Self::new(PhysicalSize::new(800, 600))
+    }
+}
+
+impl EventLoopContext for MockEventLoop {
+    fn create_target(&self, _: WindowAttributes) -> Result<RenderTarget, Error> {
+        self.created_targets.set(self.created_targets.get() + 1);
+        Ok(RenderTarget::Headless(self.size))
+    }
+
+    fn exit(&self) {
+        self.exited.set(true);
+    }
+
+    fn request_redraw(&self, _: &RenderTarget) {
+        self.redraw_requests.set(self.redraw_requests.get() + 1);
+    }
+
+    fn set_frame_timeout(&self, timeout: Option<Duration>) {
+        self.frame_timeout.set(timeout);
+    }
+}
@@ -1,23 +1,37 @@
-use std::{
-    collections::VecDeque,
-    sync::{mpsc, Arc},
-};
+use std::{rc::Rc, sync::mpsc};
 
 use winit::{
     application::ApplicationHandler,
-    event::{DeviceEvent, DeviceId, WindowEvent},
+    event::{DeviceEvent, DeviceId, StartCause, WindowEvent},
     event_loop::ActiveEventLoop,
     window::{Window, WindowAttributes, WindowId},
 };
 use winit_input_helper::WinitInputHelper;
 
-use crate::engine::{signal, InSignal, Items, SystemPipeline};
+use crate::engine::{
+    queue::{QueueCapacity, SignalQueue},
+    session::{Recorder, Replay, ReplayStep},
+    signal::{
+        InSignalReceiver, LifecycleSignal, OutSignalSender, QueryError, QueueMode, RenderInfo, Wake,
+    },
+    timestep, utils, Clock, EventLoopContext, FixedTimestep, GpuCache, InSignal, Items, Keys,
+    MonotonicClock, NextFrame, OutSignal, RenderTarget, SystemPipeline,
+};
 
 /// The main engine struct that create the window and runs the system pipeline.
 pub struct Engine<T: SystemPipeline> {
-    rx: Option<mpsc::Receiver<InSignal<T>>>,
-    tx: Option<mpsc::Sender<T::OutSignal>>,
-    queued_signals: VecDeque<T::InSignal>,
+    rx: Option<InSignalReceiver<T>>,
+    tx: Option<mpsc::Sender<OutSignal<T::OutSignal>>>,
+    queue: SignalQueue<T::InSignal>,
+    clock: Rc<dyn Clock>,
+    gpu_cache: GpuCache,
+    has_initialized: bool,
+    recorder: Option<Recorder<T>>,
+    replay: Option<Replay<T>>,
+    timestep: Option<FixedTimestep>,
+    next_frame: NextFrame,
+    is_occluded: bool,
+    is_zero_sized: bool,
     state: EngineState<T>,
 }
 
@@ -30,29 +44,162 @@ impl<T: SystemPipeline> Engine<T> {
             },
         };
 
+        let clock: Rc<dyn Clock> = Rc::new(MonotonicClock::new());
+
         Self {
             rx: None,
             tx: None,
-            queued_signals: VecDeque::new(),
+            queue: SignalQueue::new().with_clock(clock.clone()),
+            clock,
+            gpu_cache: GpuCache::new(),
+            has_initialized: false,
+            recorder: None,
+            replay: None,
+            timestep: None,
+            next_frame: NextFrame::Idle,
+            is_occluded: false,
+            is_zero_sized: false,
             state,
         }
     }
 
     /// Set the incoming signal receiver.
-    pub fn with_rx(mut self, rx: mpsc::Receiver<InSignal<T>>) -> Self {
+    pub fn with_rx(mut self, rx: InSignalReceiver<T>) -> Self {
         self.rx = Some(rx);
         self
     }
 
+    /// Set the capacity of the custom signal queue, it is unbounded by default.
+    pub fn with_queue_capacity(mut self, capacity: QueueCapacity) -> Self {
+        self.queue = self.queue.with_capacity(capacity);
+        self
+    }
+
+    /// Set the clock of the engine timers, it is the [`MonotonicClock`] by
+    /// default.
+    pub fn with_clock(mut self, clock: Rc<dyn Clock>) -> Self {
+        self.queue = self.queue.with_clock(clock.clone());
+        self.timestep = self
+            .timestep
+            .take()
+            .map(|timestep| timestep.with_clock(clock.clone()));
+        self.recorder = self
+            .recorder
+            .take()
+            .map(|recorder| recorder.with_clock(clock.clone()));
+        self.clock = clock;
+        self
+    }
+
     /// Set the outgoing signal sender.
-    pub fn with_tx(mut self, tx: mpsc::Sender<T::OutSignal>) -> Self {
+    pub fn with_tx(mut self, tx: mpsc::Sender<OutSignal<T::OutSignal>>) -> Self {
         self.tx = Some(tx);
         self
     }
-}
 
-impl<T: SystemPipeline> ApplicationHandler for Engine<T> {
-    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+    /// Set the recorder of the session.
+    ///
+    /// It measures the frame deltas with the clock of the engine, and records
+    /// the fixed timestep of the engine.
+    pub fn with_recorder(mut self, recorder: Recorder<T>) -> Self {
+        self.recorder = Some(
+            recorder
+                .with_clock(self.clock.clone())
+                .with_timestep(self.timestep.as_ref()),
+        );
+        self
+    }
+
+    /// Set the replay of a session.
+    ///
+    /// The system pipeline is fed the recording instead of the window and
+    /// device events and the incoming signals, one frame per redraw. The
+    /// engine exits when the replay is finished.
+    ///
+    /// The fixed timestep is set to the one the session was recorded with.
+    pub fn with_replay(mut self, replay: Replay<T>) -> Self {
+        if self.timestep.is_some() && !replay.matches_timestep(self.timestep.as_ref()) {
+            log::warn!("Fixed timestep replaced by the one the session was recorded with");
+        }
+
+        self.timestep = replay
+            .timestep()
+            .map(|timestep| FixedTimestep::from(timestep).with_clock(self.clock.clone()));
+        self.replay = Some(replay);
+        self
+    }
+
+    /// Set the fixed timestep of [`SystemPipeline::fixed_update`].
+    ///
+    /// It measures the frame delta with the clock of the engine. With a replay,
+    /// it is ignored if it is not the one the session was recorded with.
+    pub fn with_fixed_timestep(mut self, timestep: FixedTimestep) -> Self {
+        if let Some(replay) = &self.replay {
+            if !replay.matches_timestep(Some(&timestep)) {
+                log::warn!(
+                    "Fixed timestep ignored, it is not the one the session was recorded with"
+                );
+                return self;
+            }
+        }
+
+        self.timestep = Some(timestep.with_clock(self.clock.clone()));
+        self.recorder = self
+            .recorder
+            .take()
+            .map(|recorder| recorder.with_timestep(self.timestep.as_ref()));
+        self
+    }
+
+    /// The recorder of the session, if any.
+    pub fn recorder(&self) -> Option<&Recorder<T>> {
+        self.recorder.as_ref()
+    }
+
+    /// The current status of the engine.
+    pub fn status(&self) -> EngineStatus {
+        match &self.state {
+            EngineState::PreInit { .. } => EngineStatus::PreInit,
+            EngineState::InitializingEngine => EngineStatus::InitializingEngine,
+            EngineState::InitializingSystemPipeline { .. } => {
+                EngineStatus::InitializingSystemPipeline
+            }
+            EngineState::PostInit { .. } => EngineStatus::PostInit,
+            EngineState::Paused { .. } => EngineStatus::Paused,
+            EngineState::Stopped { .. } => EngineStatus::Stopped,
+        }
+    }
+
+    /// Whether rendering is suspended, because the render target is occluded
+    /// or has a zero size, e.g. when minimized.
+    pub fn is_suspended(&self) -> bool {
+        self.is_occluded || self.is_zero_sized
+    }
+
+    /// The system pipeline, if the engine is initialized.
+    pub fn system_pipeline(&self) -> Option<&T> {
+        match &self.state {
+            EngineState::PostInit {
+                system_pipeline, ..
+            }
+            | EngineState::Paused {
+                system_pipeline, ..
+            } => Some(system_pipeline),
+            _ => None,
+        }
+    }
+
+    /// The custom signals queued until the next frame, or until the engine
+    /// is restarted.
+    pub fn queued_signals(&self) -> &SignalQueue<T::InSignal> {
+        &self.queue
+    }
+
+    /// Handle the event loop being resumed.
+    ///
+    /// This creates the render target and starts initializing the system
+    /// pipeline.
+    pub fn on_resumed(&mut self, ctx: &impl EventLoopContext) {
         // Already initialized
         if !matches!(&self.state, EngineState::PreInit { .. }) {
             log::warn!("Engine already initializing or initialized");
@@ -65,35 +212,37 @@ impl<T: SystemPipeline> ApplicationHandler for Engine<T> {
             system_pipeline_args,
         } = self.state.initialize_engine();
 
-        // Set up window and control flow
-        let window = Arc::new(event_loop.create_window(window_attributes).unwrap());
-
-        #[cfg(target_arch = "wasm32")]
-        {
-            use winit::platform::web::WindowExtWebSys;
+        // Set up render target
+        let target = match ctx.create_target(window_attributes) {
+            Ok(target) => target,
+            Err(e) => {
+                log::error!("Engine failed to create render target: {e}");
+                self.fail(ctx, None, e.to_string());
+                return;
+            }
+        };
 
-            window.set_prevent_default(true);
+        log::debug!("Render target created");
+        self.is_occluded = false;
+        self.is_zero_sized = false;
+        if let Some(recorder) = &mut self.recorder {
+            recorder.start(&system_pipeline_args, target.size());
         }
-
-        log::debug!("Window created");
+        self.send_lifecycle(LifecycleSignal::Initializing);
 
         // Initialize system pipeline
+        let gpu_cache = self.gpu_cache.clone();
         let (tx, rx) = mpsc::channel();
         let init_fn = async move {
-            let system_pipeline = T::init(window.clone(), system_pipeline_args).await;
-            tx.send((window.clone(), system_pipeline)).unwrap();
-            window.request_redraw();
+            let system_pipeline = T::init(target.clone(), gpu_cache, system_pipeline_args).await;
+            target.request_redraw();
+            if tx.send((target, system_pipeline)).is_err() {
+                log::warn!("Engine dropped before system pipeline initialized");
+            }
         };
 
         log::debug!("Spawning system pipeline initialization future");
-
-        cfg_if::cfg_if! {
-            if #[cfg(target_arch = "wasm32")] {
-                wasm_bindgen_futures::spawn_local(init_fn);
-            } else {
-                futures::executor::block_on(init_fn);
-            }
-        }
+        utils::spawn(init_fn);
 
         // Update state
         self.state = EngineState::InitializingSystemPipeline {
@@ -104,31 +253,93 @@ impl<T: SystemPipeline> ApplicationHandler for Engine<T> {
         log::info!("System pipeline initializing asynchronously");
     }
 
-    fn window_event(&mut self, event_loop: &ActiveEventLoop, _: WindowId, event: WindowEvent) {
+    /// Handle a [`WindowEvent`].
+    pub fn on_window_event(&mut self, ctx: &impl EventLoopContext, event: WindowEvent) {
         // Shut down if the window is closed
         if let WindowEvent::CloseRequested = event {
             log::info!("Engine exiting");
-            event_loop.exit();
+            ctx.exit();
+        }
+
+        // Replay the session instead of the window events
+        if self.replay.is_some() && matches!(self.state, EngineState::PostInit { .. }) {
+            if let WindowEvent::RedrawRequested = event {
+                self.on_replay_step(ctx);
+            }
+            return;
         }
 
+        self.on_visibility(ctx, &event);
+        let is_suspended = self.is_suspended();
+
         match &mut self.state {
             EngineState::InitializingSystemPipeline { init_rx, input } => {
-                input.window_event(&event);
+                if self.replay.is_none() {
+                    input.window_event(&event);
+                }
 
                 // Wait for the system pipeline to initialize
                 if let WindowEvent::RedrawRequested = event {
-                    if let Ok((window, system_pipeline)) = init_rx.try_recv() {
-                        window.request_redraw();
+                    match init_rx.try_recv() {
+                        Ok((target, Ok(mut system_pipeline))) => {
+                            let info = RenderInfo::new(
+                                &target,
+                                &self.gpu_cache,
+                                system_pipeline.surface_format(),
+                            );
 
-                        self.state = EngineState::PostInit {
-                            items: Items::<T::OutSignal> {
-                                window,
+                            if let Some(timestep) = &mut self.timestep {
+                                timestep.reset();
+                            }
+
+                            let mut items = Items::<T::OutSignal> {
+                                target,
+                                gpu_cache: self.gpu_cache.clone(),
                                 input: std::mem::take(input),
-                                tx: self.tx.clone(),
-                            },
-                            system_pipeline,
-                        };
-                        log::info!("Engine initialized")
+                                keys: Keys::new(),
+                                cursor_locked: false,
+                                is_replay: false,
+                                tx: self.tx.clone().map(OutSignalSender::new),
+                                frame_delta: None,
+                                fixed_step: None,
+                                next_frame: NextFrame::Idle,
+                            };
+
+                            // Hidden while initializing, so pause as if suspended
+                            // now, for the resume when the render target is visible
+                            match is_suspended {
+                                true => {
+                                    log::info!("Engine suspending, render target hidden");
+                                    if let Some(recorder) = &mut self.recorder {
+                                        recorder.pause();
+                                    }
+                                    system_pipeline.pause(&mut items);
+                                }
+                                false => schedule_frame(
+                                    ctx,
+                                    &mut self.next_frame,
+                                    &items.target,
+                                    NextFrame::Now,
+                                ),
+                            }
+
+                            self.state = EngineState::PostInit {
+                                items,
+                                system_pipeline,
+                            };
+                            log::info!("Engine initialized");
+
+                            self.send_lifecycle(match self.has_initialized {
+                                true => LifecycleSignal::Restarted(info),
+                                false => LifecycleSignal::Initialized(info),
+                            });
+                            self.has_initialized = true;
+                        }
+                        Ok((target, Err(e))) => {
+                            log::error!("System pipeline failed to initialize: {e}");
+                            self.fail(ctx, Some(target), e.to_string());
+                        }
+                        Err(..) => {}
                     }
                 }
             }
@@ -136,91 +347,381 @@ impl<T: SystemPipeline> ApplicationHandler for Engine<T> {
                 items,
                 system_pipeline,
             } => {
+                if let Some(recorder) = &mut self.recorder {
+                    recorder.window_event(&event);
+                }
+
                 // Call system pipeline `window_event`
                 system_pipeline.window_event(items, &event);
 
                 items.input.window_event(&event);
+                items.keys.window_event(&event);
 
-                if let WindowEvent::RedrawRequested = event {
-                    // Call system pipeline `update`
-                    system_pipeline.update(items);
+                if let (WindowEvent::RedrawRequested, false) = (&event, is_suspended) {
+                    // Flush queued signals once per frame
+                    for signal in self.queue.drain() {
+                        if let Some(recorder) = &mut self.recorder {
+                            recorder.signal(&signal);
+                        }
+                        system_pipeline.in_signal(items, signal);
+                    }
+
+                    // Call system pipeline `fixed_update` and `update`
+                    items.frame_delta = self.recorder.as_mut().map(Recorder::frame);
+                    timestep::update(system_pipeline, items, self.timestep.as_mut());
+                    if let Some(recorder) = &mut self.recorder {
+                        recorder.end_frame(items);
+                    }
 
                     items.input.end_step();
                     items.input.new_events();
-                }
+                    items.keys.end_frame();
 
-                // Handle queued events
-                while let Some(signal) = self.queued_signals.pop_front() {
-                    system_pipeline.in_signal(items, signal);
+                    let next_frame = std::mem::take(&mut items.next_frame);
+                    schedule_frame(ctx, &mut self.next_frame, &items.target, next_frame);
+                } else if items.next_frame != NextFrame::Idle && !is_suspended {
+                    // A frame requested on the event, e.g. with on-demand redraws
+                    let next_frame = std::mem::take(&mut items.next_frame);
+                    schedule_frame(ctx, &mut self.next_frame, &items.target, next_frame);
+                }
+            }
+            EngineState::Paused {
+                items,
+                system_pipeline,
+            } => {
+                if let Some(recorder) = &mut self.recorder {
+                    recorder.window_event(&event);
                 }
 
-                // Handle incoming events
-                if let Some(rx) = &self.rx {
-                    for signal in rx.try_iter() {
-                        match signal {
-                            InSignal::Stop => {
-                                log::info!("Engine stopping");
-                                self.state = EngineState::Stopped {
-                                    window: items.window.clone(),
-                                };
-                                return;
+                // Keep track of the window, but do not update
+                system_pipeline.window_event(items, &event);
+
+                items.input.window_event(&event);
+                items.keys.window_event(&event);
+            }
+            EngineState::Stopped { .. } => {}
+            state => log::error!("Engine in unexpected state: {state:?}"),
+        }
+
+        self.on_in_signals(ctx);
+    }
+
+    /// Handle the incoming signals.
+    ///
+    /// This is called when the engine is woken by an
+    /// [`crate::engine::signal::InSignalSender`], and
+    /// after every [`WindowEvent`]. Signals are left in the channel while the
+    /// engine is initializing.
+    ///
+    /// When running, custom signals are queued and passed to the system
+    /// pipeline once per frame. When paused, they are passed at once.
+    ///
+    /// Incoming signals are not handled when replaying a session.
+    pub fn on_in_signals(&mut self, ctx: &impl EventLoopContext) {
+        if self.replay.is_some() {
+            return;
+        }
+
+        if self.rx.is_none() {
+            return;
+        }
+
+        // Handle incoming events
+        loop {
+            if !matches!(
+                self.state,
+                EngineState::PostInit { .. }
+                    | EngineState::Paused { .. }
+                    | EngineState::Stopped { .. }
+            ) {
+                return;
+            }
+
+            let Some(signal) = self.rx.as_ref().and_then(|rx| rx.try_recv().ok()) else {
+                return;
+            };
+            let is_suspended = self.is_suspended();
+
+            match &mut self.state {
+                EngineState::PostInit {
+                    items,
+                    system_pipeline,
+                } => match signal {
+                    InSignal::Stop => {
+                        log::info!("Engine stopping");
+                        if let Some(recorder) = &mut self.recorder {
+                            recorder.stop();
+                        }
+                        self.queue.remove_ignored();
+                        self.state = EngineState::Stopped {
+                            target: Some(items.target.clone()),
+                        };
+                        self.send_lifecycle(LifecycleSignal::Stopped);
+                    }
+                    InSignal::Pause => {
+                        log::info!("Engine pausing");
+                        for signal in self.queue.drain() {
+                            if let Some(recorder) = &mut self.recorder {
+                                recorder.signal(&signal);
                             }
-                            InSignal::Start { .. } => log::warn!("Engine already started"),
-                            InSignal::Custom { signal, .. } => {
-                                system_pipeline.in_signal(items, signal)
+                            system_pipeline.in_signal(items, signal);
+                        }
+                        // Already paused while suspended
+                        if !is_suspended {
+                            if let Some(recorder) = &mut self.recorder {
+                                recorder.pause();
                             }
+                            system_pipeline.pause(items);
                         }
+                        self.state.pause();
+                        self.send_lifecycle(LifecycleSignal::Paused);
                     }
-                }
-            }
-            EngineState::Stopped { window } => {
-                // Handle incoming events
-                if let Some(rx) = &self.rx {
-                    for signal in rx.try_iter() {
-                        match signal {
-                            InSignal::Start {
+                    InSignal::Start { .. } => log::warn!("Engine already started"),
+                    InSignal::Resume => log::warn!("Engine not paused"),
+                    InSignal::Custom { signal, queue } => {
+                        self.queue.push(signal, queue);
+
+                        // Wake the engine if no frame is coming to handle it
+                        if self.next_frame == NextFrame::Idle && !is_suspended {
+                            schedule_frame(
+                                ctx,
+                                &mut self.next_frame,
+                                &items.target,
+                                NextFrame::Now,
+                            );
+                        }
+                    }
+                    InSignal::Query { id, query } => {
+                        let result = system_pipeline.query(items, query);
+                        self.send(OutSignal::from_query_result(id, result));
+                    }
+                },
+                EngineState::Paused {
+                    items,
+                    system_pipeline,
+                } => match signal {
+                    InSignal::Stop => {
+                        log::info!("Engine stopping");
+                        if let Some(recorder) = &mut self.recorder {
+                            recorder.stop();
+                        }
+                        self.state = EngineState::Stopped {
+                            target: Some(items.target.clone()),
+                        };
+                        self.send_lifecycle(LifecycleSignal::Stopped);
+                    }
+                    InSignal::Resume => {
+                        log::info!("Engine resuming");
+                        // Stay paused until unsuspended
+                        if !is_suspended {
+                            if let Some(recorder) = &mut self.recorder {
+                                recorder.resume();
+                            }
+                            if let Some(timestep) = &mut self.timestep {
+                                timestep.reset();
+                            }
+                            system_pipeline.resume(items);
+                            schedule_frame(
+                                ctx,
+                                &mut self.next_frame,
+                                &items.target,
+                                NextFrame::Now,
+                            );
+                        }
+                        self.state.resume();
+                        self.send_lifecycle(LifecycleSignal::Resumed);
+                    }
+                    InSignal::Start { .. } => log::warn!("Engine already started"),
+                    InSignal::Pause => log::warn!("Engine already paused"),
+                    InSignal::Custom { signal, .. } => {
+                        if let Some(recorder) = &mut self.recorder {
+                            recorder.signal(&signal);
+                        }
+                        system_pipeline.in_signal(items, signal);
+                    }
+                    InSignal::Query { id, query } => {
+                        let result = system_pipeline.query(items, query);
+                        self.send(OutSignal::from_query_result(id, result));
+                    }
+                },
+                EngineState::Stopped { .. } => match signal {
+                    InSignal::Start {
+                        window_attributes,
+                        system_pipeline_args,
+                    } => {
+                        log::info!("Engine restarting");
+                        self.state = EngineState::PreInit {
+                            items: PreInitItems {
                                 window_attributes,
                                 system_pipeline_args,
-                            } => {
-                                log::info!("Engine restarting");
-                                self.state = EngineState::PreInit {
-                                    items: PreInitItems {
-                                        window_attributes,
-                                        system_pipeline_args,
-                                    },
-                                };
-                                self.resumed(event_loop);
-                                return;
-                            }
-                            InSignal::Stop => log::warn!("Engine already stopped"),
-                            InSignal::Custom { signal, queue } => match queue {
-                                signal::QueueBehavior::Replace(pred) => {
-                                    self.queued_signals.retain(|x| !pred(x, &signal));
-                                    self.queued_signals.push_back(signal)
-                                }
-                                signal::QueueBehavior::Queued => {
-                                    self.queued_signals.push_back(signal)
-                                }
-                                signal::QueueBehavior::Ignored => {}
                             },
-                        }
+                        };
+                        self.on_resumed(ctx);
                     }
+                    InSignal::Stop => log::warn!("Engine already stopped"),
+                    InSignal::Pause | InSignal::Resume => log::warn!("Engine stopped"),
+                    InSignal::Custom { signal, queue } => match queue.mode {
+                        QueueMode::Ignored => {}
+                        _ => self.queue.push(signal, queue),
+                    },
+                    InSignal::Query { id, .. } => self.send(OutSignal::QueryFailed {
+                        id,
+                        error: QueryError::Stopped,
+                    }),
+                },
+                state => {
+                    log::error!("Engine in unexpected state: {state:?}");
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Replay the session up to the next frame.
+    fn on_replay_step(&mut self, ctx: &impl EventLoopContext) {
+        let (
+            Some(replay),
+            EngineState::PostInit {
+                items,
+                system_pipeline,
+            },
+        ) = (&mut self.replay, &mut self.state)
+        else {
+            return;
+        };
+
+        match replay.step(system_pipeline, items, self.timestep.as_mut()) {
+            ReplayStep::Frame => {
+                let next_frame = std::mem::take(&mut items.next_frame);
+                schedule_frame(ctx, &mut self.next_frame, &items.target, next_frame);
+            }
+            ReplayStep::Restart { args, size } => {
+                log::info!("Engine restarting for replay");
+                let window_attributes = match items.target.window() {
+                    Some(window) => Window::default_attributes().with_title(window.title()),
+                    None => Window::default_attributes(),
+                };
 
-                    window.request_redraw();
-                } else {
-                    panic!("Engine stopped without incoming signal receiver");
+                self.send_lifecycle(LifecycleSignal::Stopped);
+                self.state = EngineState::PreInit {
+                    items: PreInitItems {
+                        window_attributes: window_attributes.with_inner_size(size),
+                        system_pipeline_args: args,
+                    },
+                };
+                self.on_resumed(ctx);
+            }
+            ReplayStep::Finished => {
+                log::info!("Replay finished, engine exiting");
+                ctx.exit();
+            }
+        }
+    }
+
+    /// Handle the timeout of a [`NextFrame::After`] request, redrawing if the
+    /// engine is still running.
+    pub fn on_frame_timeout(&mut self, ctx: &impl EventLoopContext) {
+        match &self.state {
+            EngineState::PostInit { items, .. } if !self.is_suspended() => {
+                schedule_frame(ctx, &mut self.next_frame, &items.target, NextFrame::Now)
+            }
+            _ => {
+                ctx.set_frame_timeout(None);
+                self.next_frame = NextFrame::Idle;
+            }
+        }
+    }
+
+    /// Suspend rendering when the render target is hidden, and unsuspend it
+    /// when it is visible again.
+    ///
+    /// On the web, winit reports the page visibility and the intersection of
+    /// the canvas with the viewport as [`WindowEvent::Occluded`]. The system
+    /// pipeline is paused meanwhile, so the time hidden is not in the delta of
+    /// the next frame. If it is hidden while initializing, the system pipeline
+    /// is paused once initialized instead.
+    fn on_visibility(&mut self, ctx: &impl EventLoopContext, event: &WindowEvent) {
+        let was_suspended = self.is_suspended();
+        match event {
+            WindowEvent::Occluded(is_occluded) => self.is_occluded = *is_occluded,
+            WindowEvent::Resized(size) => self.is_zero_sized = size.width == 0 || size.height == 0,
+            _ => return,
+        }
+
+        let is_suspended = self.is_suspended();
+        if was_suspended == is_suspended {
+            return;
+        }
+
+        if let EngineState::PostInit {
+            items,
+            system_pipeline,
+        } = &mut self.state
+        {
+            match is_suspended {
+                true => {
+                    log::info!("Engine suspending, render target hidden");
+                    if let Some(recorder) = &mut self.recorder {
+                        recorder.pause();
+                    }
+                    system_pipeline.pause(items);
+                    ctx.set_frame_timeout(None);
+                    self.next_frame = NextFrame::Idle;
+                }
+                false => {
+                    log::info!("Engine unsuspending, render target visible");
+                    if let Some(recorder) = &mut self.recorder {
+                        recorder.resume();
+                    }
+                    if let Some(timestep) = &mut self.timestep {
+                        timestep.reset();
+                    }
+                    system_pipeline.resume(items);
+                    schedule_frame(ctx, &mut self.next_frame, &items.target, NextFrame::Now);
                 }
             }
-            state => log::error!("Engine in unexpected state: {state:?}"),
+        }
+
+        self.send_lifecycle(match is_suspended {
+            true => LifecycleSignal::Occluded,
+            false => LifecycleSignal::Visible,
+        });
+    }
+
+    /// Stop the engine after it failed to initialize, reporting the error.
+    ///
+    /// Without an incoming signal receiver nothing can restart the engine, so
+    /// the event loop is exited instead of idling.
+    fn fail(&mut self, ctx: &impl EventLoopContext, target: Option<RenderTarget>, error: String) {
+        self.state = EngineState::Stopped { target };
+        self.send_lifecycle(LifecycleSignal::Failed { error });
+
+        if self.rx.is_none() {
+            log::error!("Engine stopped without incoming signal receiver, exiting");
+            ctx.exit();
+        }
+    }
+
+    /// Send a [`LifecycleSignal`] to outside the engine.
+    fn send_lifecycle(&self, signal: LifecycleSignal) {
+        self.send(OutSignal::Lifecycle(signal));
+    }
+
+    /// Send an [`OutSignal`] to outside the engine.
+    fn send(&self, signal: OutSignal<T::OutSignal>) {
+        if let Some(tx) = &self.tx {
+            if tx.send(signal).is_err() {
+                log::warn!("Outgoing signal receiver dropped");
+            }
         }
     }
 
-    fn device_event(
-        &mut self,
-        _event_loop: &ActiveEventLoop,
-        _device_id: DeviceId,
-        event: DeviceEvent,
-    ) {
+    /// Handle a [`DeviceEvent`].
+    pub fn on_device_event(&mut self, ctx: &impl EventLoopContext, event: DeviceEvent) {
+        if self.replay.is_some() {
+            return;
+        }
+
         match &mut self.state {
             EngineState::InitializingSystemPipeline { input, .. } => {
                 input.device_event(&event);
@@ -230,16 +731,94 @@ impl<T: SystemPipeline> ApplicationHandler for Engine<T> {
                 system_pipeline,
                 ..
             } => {
+                if let Some(recorder) = &mut self.recorder {
+                    recorder.device_event(&event);
+                }
+
                 items.input.device_event(&event);
 
                 // Call system pipeline `device_event`
                 system_pipeline.device_event(items, &event);
+
+                // A frame requested on the event, e.g. with on-demand redraws
+                if items.next_frame != NextFrame::Idle {
+                    let next_frame = std::mem::take(&mut items.next_frame);
+                    schedule_frame(ctx, &mut self.next_frame, &items.target, next_frame);
+                }
             }
             _ => {}
         }
     }
 }
 
+impl<T: SystemPipeline> ApplicationHandler<Wake> for Engine<T> {
+    fn new_events(&mut self, event_loop: &ActiveEventLoop, cause: StartCause) {
+        if let StartCause::ResumeTimeReached { .. } = cause {
+            self.on_frame_timeout(event_loop);
+        }
+    }
+
+    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+        self.on_resumed(event_loop);
+    }
+
+    fn window_event(&mut self, event_loop: &ActiveEventLoop, _: WindowId, event: WindowEvent) {
+        self.on_window_event(event_loop, event);
+    }
+
+    fn device_event(&mut self, event_loop: &ActiveEventLoop, _: DeviceId, event: DeviceEvent) {
+        self.on_device_event(event_loop, event);
+    }
+
+    fn user_event(&mut self, event_loop: &ActiveEventLoop, _: Wake) {
+        self.on_in_signals(event_loop);
+    }
+
+    fn exiting(&mut self, _: &ActiveEventLoop) {
+        if let Some(recorder) = &self.recorder {
+            if let Err(e) = recorder.save() {
+                log::error!("Engine failed to save session: {e}");
+            }
+        }
+    }
+}
+
+/// Request the next frame of the render target from the event loop, keeping
+/// it in `pending`.
+fn schedule_frame(
+    ctx: &impl EventLoopContext,
+    pending: &mut NextFrame,
+    target: &RenderTarget,
+    next_frame: NextFrame,
+) {
+    *pending = next_frame;
+    match next_frame {
+        NextFrame::Idle => ctx.set_frame_timeout(None),
+        NextFrame::After(timeout) => ctx.set_frame_timeout(Some(timeout)),
+        NextFrame::Now => {
+            ctx.set_frame_timeout(None);
+            ctx.request_redraw(target);
+        }
+    }
+}
+
+/// The status of the [`Engine`], mirroring its internal state.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EngineStatus {
+    /// The event loop is not resumed yet, or the engine is restarting.
+    PreInit,
+    /// The render target is being created.
+    InitializingEngine,
+    /// The system pipeline is initializing asynchronously.
+    InitializingSystemPipeline,
+    /// The system pipeline is running.
+    PostInit,
+    /// The system pipeline is kept alive, but not updated.
+    Paused,
+    /// The system pipeline is dropped, or failed to initialize.
+    Stopped,
+}
+
 struct PreInitItems<T> {
     window_attributes: WindowAttributes,
     system_pipeline_args: T,
@@ -252,15 +831,19 @@ enum EngineState<T: SystemPipeline> {
     },
     InitializingEngine,
     InitializingSystemPipeline {
-        init_rx: mpsc::Receiver<(Arc<Window>, T)>,
+        init_rx: mpsc::Receiver<(RenderTarget, Result<T, T::Error>)>,
         input: Box<WinitInputHelper>,
     },
     PostInit {
         items: Items<T::OutSignal>,
         system_pipeline: T,
     },
+    Paused {
+        items: Items<T::OutSignal>,
+        system_pipeline: T,
+    },
     Stopped {
-        window: Arc<Window>,
+        target: Option<RenderTarget>,
     },
 }
 
@@ -273,6 +856,34 @@ impl<T: SystemPipeline> EngineState<T> {
             state => panic!("Expected `PreInit`, found {state:?}"),
         }
     }
+
+    /// Go from [`EngineState::PostInit`] into [`EngineState::Paused`].
+    fn pause(&mut self) {
+        *self = match std::mem::replace(self, Self::InitializingEngine) {
+            Self::PostInit {
+                items,
+                system_pipeline,
+            } => Self::Paused {
+                items,
+                system_pipeline,
+            },
+            state => panic!("Expected `PostInit`, found {state:?}"),
+        }
+    }
+
+    /// Go from [`EngineState::Paused`] into [`EngineState::PostInit`].
+    fn resume(&mut self) {
+        *self = match std::mem::replace(self, Self::InitializingEngine) {
+            Self::Paused {
+                items,
+                system_pipeline,
+            } => Self::PostInit {
+                items,
+                system_pipeline,
+            },
+            state => panic!("Expected `Paused`, found {state:?}"),
+        }
+    }
 }
 
 impl<T: SystemPipeline> std::fmt::Debug for EngineState<T> {
@@ -284,7 +895,401 @@ impl<T: SystemPipeline> std::fmt::Debug for EngineState<T> {
                 write!(f, "EngineState::InitializingSystemPipeline")
             }
             Self::PostInit { .. } => write!(f, "EngineState::PostInit"),
+            Self::Paused { .. } => write!(f, "EngineState::Paused"),
             Self::Stopped { .. } => write!(f, "EngineState::Stopped"),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use winit::dpi::PhysicalSize;
+
+    use crate::engine::{
+        context::MockEventLoop,
+        signal::{self, InSignalSender, QueryId, QueueBehavior},
+    };
+
+    use super::*;
+
+    /// System pipeline without a GPU context, keeping track of its calls.
+    #[derive(Debug, Default)]
+    struct TestPipeline {
+        updates: u32,
+        pauses: u32,
+        resumes: u32,
+        signals: Vec<u32>,
+    }
+
+    #[derive(Debug, thiserror::Error)]
+    #[error("test pipeline failed to initialize")]
+    struct TestError;
+
+    impl SystemPipeline for TestPipeline {
+        /// Whether to fail to initialize.
+        type Args = bool;
+        type InSignal = u32;
+        type OutSignal = ();
+        type Query = ();
+        type Error = TestError;
+
+        async fn init(_: RenderTarget, _: GpuCache, fail: bool) -> Result<Self, TestError> {
+            match fail {
+                true => Err(TestError),
+                false => Ok(Self::default()),
+            }
+        }
+
+        fn update(&mut self, _: &mut Items<()>) {
+            self.updates += 1;
+        }
+
+        fn pause(&mut self, _: &mut Items<()>) {
+            self.pauses += 1;
+        }
+
+        fn resume(&mut self, _: &mut Items<()>) {
+            self.resumes += 1;
+        }
+
+        fn in_signal(&mut self, _: &mut Items<()>, signal: u32) {
+            self.signals.push(signal);
+        }
+    }
+
+    struct Harness {
+        ctx: MockEventLoop,
+        engine: Engine<TestPipeline>,
+        tx: InSignalSender<TestPipeline>,
+        out_rx: mpsc::Receiver<OutSignal<()>>,
+    }
+
+    impl Harness {
+        fn new(fail: bool) -> Self {
+            let (tx, rx) = signal::channel();
+            let (out_tx, out_rx) = mpsc::channel();
+
+            Self {
+                ctx: MockEventLoop::new(PhysicalSize::new(64, 64)),
+                engine: Engine::new(Window::default_attributes(), fail)
+                    .with_rx(rx)
+                    .with_tx(out_tx),
+                tx,
+                out_rx,
+            }
+        }
+
+        /// Resume the event loop and redraw until the system pipeline is
+        /// initialized.
+        fn start(&mut self) {
+            self.engine.on_resumed(&self.ctx);
+            self.redraw();
+        }
+
+        fn redraw(&mut self) {
+            self.engine
+                .on_window_event(&self.ctx, WindowEvent::RedrawRequested);
+        }
+
+        fn send(&mut self, signal: InSignal<TestPipeline>) {
+            self.tx.send(signal).expect("engine receiver");
+            self.engine.on_in_signals(&self.ctx);
+        }
+
+        fn restart(&mut self) {
+            self.send(InSignal::Start {
+                window_attributes: Window::default_attributes(),
+                system_pipeline_args: false,
+            });
+            self.redraw();
+        }
+
+        fn custom(&mut self, signal: u32, queue: QueueBehavior<u32>) {
+            self.send(InSignal::Custom { signal, queue });
+        }
+
+        fn pipeline(&self) -> &TestPipeline {
+            self.engine.system_pipeline().expect("system pipeline")
+        }
+
+        /// The lifecycle signals sent since the last call.
+        fn lifecycle(&self) -> Vec<LifecycleSignal> {
+            self.out_rx
+                .try_iter()
+                .filter_map(|signal| match signal {
+                    OutSignal::Lifecycle(signal) => Some(signal),
+                    _ => None,
+                })
+                .collect()
+        }
+    }
+
+    #[test]
+    fn start_initializes_on_redraw() {
+        let mut harness = Harness::new(false);
+        assert_eq!(harness.engine.status(), EngineStatus::PreInit);
+
+        harness.engine.on_resumed(&harness.ctx);
+        assert_eq!(
+            harness.engine.status(),
+            EngineStatus::InitializingSystemPipeline
+        );
+
+        harness.redraw();
+        assert_eq!(harness.engine.status(), EngineStatus::PostInit);
+        assert_eq!(harness.ctx.created_targets(), 1);
+        assert!(matches!(
+            harness.lifecycle().as_slice(),
+            [
+                LifecycleSignal::Initializing,
+                LifecycleSignal::Initialized(..)
+            ]
+        ));
+
+        harness.redraw();
+        assert_eq!(harness.pipeline().updates, 1);
+    }
+
+    #[test]
+    fn pause_and_resume_keep_system_pipeline() {
+        let mut harness = Harness::new(false);
+        harness.start();
+        harness.lifecycle();
+
+        harness.send(InSignal::Pause);
+        assert_eq!(harness.engine.status(), EngineStatus::Paused);
+        assert_eq!(harness.pipeline().pauses, 1);
+
+        // No update while paused, but signals are passed at once
+        harness.redraw();
+        harness.custom(1, QueueBehavior::queued());
+        assert_eq!(harness.pipeline().updates, 0);
+        assert_eq!(harness.pipeline().signals, [1]);
+
+        let redraw_requests = harness.ctx.redraw_requests();
+        harness.send(InSignal::Resume);
+        assert_eq!(harness.engine.status(), EngineStatus::PostInit);
+        assert_eq!(harness.pipeline().resumes, 1);
+        assert_eq!(harness.ctx.redraw_requests(), redraw_requests + 1);
+        assert!(matches!(
+            harness.lifecycle().as_slice(),
+            [LifecycleSignal::Paused, LifecycleSignal::Resumed]
+        ));
+    }
+
+    #[test]
+    fn stop_and_restart_recreate_system_pipeline() {
+        let mut harness = Harness::new(false);
+        harness.start();
+        harness.redraw();
+        harness.lifecycle();
+
+        harness.send(InSignal::Stop);
+        assert_eq!(harness.engine.status(), EngineStatus::Stopped);
+        assert!(harness.engine.system_pipeline().is_none());
+
+        harness.restart();
+        assert_eq!(harness.engine.status(), EngineStatus::PostInit);
+        assert_eq!(harness.ctx.created_targets(), 2);
+        assert_eq!(harness.pipeline().updates, 0);
+        assert!(matches!(
+            harness.lifecycle().as_slice(),
+            [
+                LifecycleSignal::Stopped,
+                LifecycleSignal::Initializing,
+                LifecycleSignal::Restarted(..)
+            ]
+        ));
+    }
+
+    #[test]
+    fn stop_from_pause() {
+        let mut harness = Harness::new(false);
+        harness.start();
+
+        harness.send(InSignal::Pause);
+        harness.send(InSignal::Stop);
+        assert_eq!(harness.engine.status(), EngineStatus::Stopped);
+
+        harness.restart();
+        assert_eq!(harness.engine.status(), EngineStatus::PostInit);
+    }
+
+    #[test]
+    fn queued_signals_replayed_after_restart() {
+        let mut harness = Harness::new(false);
+        harness.start();
+        harness.send(InSignal::Stop);
+
+        harness.custom(1, QueueBehavior::queued());
+        harness.custom(2, QueueBehavior::ignored());
+        harness.custom(3, QueueBehavior::queued());
+        assert_eq!(
+            harness.engine.queued_signals().iter().collect::<Vec<_>>(),
+            [&1, &3]
+        );
+
+        harness.restart();
+        assert!(harness.pipeline().signals.is_empty());
+
+        // Flushed on the first frame
+        harness.redraw();
+        assert_eq!(harness.pipeline().signals, [1, 3]);
+        assert!(harness.engine.queued_signals().is_empty());
+    }
+
+    #[test]
+    fn ignored_signals_dropped_on_stop() {
+        let mut harness = Harness::new(false);
+        harness.start();
+
+        harness.custom(1, QueueBehavior::ignored());
+        harness.custom(2, QueueBehavior::queued());
+        harness.send(InSignal::Stop);
+        assert_eq!(
+            harness.engine.queued_signals().iter().collect::<Vec<_>>(),
+            [&2]
+        );
+    }
+
+    #[test]
+    fn unexpected_signals_keep_state() {
+        let mut harness = Harness::new(false);
+        harness.start();
+
+        harness.send(InSignal::Resume);
+        harness.send(InSignal::Start {
+            window_attributes: Window::default_attributes(),
+            system_pipeline_args: false,
+        });
+        assert_eq!(harness.engine.status(), EngineStatus::PostInit);
+        assert_eq!(harness.ctx.created_targets(), 1);
+
+        harness.send(InSignal::Pause);
+        harness.send(InSignal::Pause);
+        assert_eq!(harness.engine.status(), EngineStatus::Paused);
+        assert_eq!(harness.pipeline().pauses, 1);
+
+        harness.send(InSignal::Stop);
+        harness.send(InSignal::Stop);
+        harness.send(InSignal::Pause);
+        harness.send(InSignal::Resume);
+        assert_eq!(harness.engine.status(), EngineStatus::Stopped);
+        assert!(!harness.ctx.exited());
+    }
+
+    #[test]
+    fn resumed_twice_creates_one_target() {
+        let mut harness = Harness::new(false);
+        harness.start();
+
+        harness.engine.on_resumed(&harness.ctx);
+        assert_eq!(harness.engine.status(), EngineStatus::PostInit);
+        assert_eq!(harness.ctx.created_targets(), 1);
+    }
+
+    #[test]
+    fn occlusion_pauses_and_resumes_system_pipeline() {
+        let mut harness = Harness::new(false);
+        harness.start();
+        harness.lifecycle();
+
+        harness
+            .engine
+            .on_window_event(&harness.ctx, WindowEvent::Occluded(true));
+        harness.redraw();
+        assert_eq!(harness.pipeline().pauses, 1);
+        assert_eq!(harness.pipeline().updates, 0);
+
+        harness
+            .engine
+            .on_window_event(&harness.ctx, WindowEvent::Occluded(false));
+        assert_eq!(harness.pipeline().resumes, 1);
+        assert!(matches!(
+            harness.lifecycle().as_slice(),
+            [LifecycleSignal::Occluded, LifecycleSignal::Visible]
+        ));
+    }
+
+    #[test]
+    fn occluded_while_initializing_pauses_once_initialized() {
+        let mut harness = Harness::new(false);
+        harness.engine.on_resumed(&harness.ctx);
+        harness
+            .engine
+            .on_window_event(&harness.ctx, WindowEvent::Occluded(true));
+
+        harness.redraw();
+        assert_eq!(harness.engine.status(), EngineStatus::PostInit);
+        assert_eq!(harness.pipeline().pauses, 1);
+        assert_eq!(harness.pipeline().resumes, 0);
+
+        harness
+            .engine
+            .on_window_event(&harness.ctx, WindowEvent::Occluded(false));
+        assert_eq!(harness.pipeline().pauses, 1);
+        assert_eq!(harness.pipeline().resumes, 1);
+        assert!(matches!(
+            harness.lifecycle().as_slice(),
+            [
+                LifecycleSignal::Initializing,
+                LifecycleSignal::Occluded,
+                LifecycleSignal::Initialized(..),
+                LifecycleSignal::Visible
+            ]
+        ));
+    }
+
+    #[test]
+    fn query_fails_when_stopped() {
+        let mut harness = Harness::new(false);
+        harness.start();
+        harness.send(InSignal::Stop);
+
+        harness.send(InSignal::Query {
+            id: QueryId(0),
+            query: (),
+        });
+        assert!(harness.out_rx.try_iter().any(|signal| matches!(
+            signal,
+            OutSignal::QueryFailed {
+                id: QueryId(0),
+                error: QueryError::Stopped,
+            }
+        )));
+    }
+
+    #[test]
+    fn init_failure_stops_engine() {
+        let mut harness = Harness::new(true);
+        harness.start();
+
+        assert_eq!(harness.engine.status(), EngineStatus::Stopped);
+        assert!(!harness.ctx.exited());
+        assert!(matches!(
+            harness.lifecycle().as_slice(),
+            [
+                LifecycleSignal::Initializing,
+                LifecycleSignal::Failed { .. }
+            ]
+        ));
+
+        // Restarted by the host
+        harness.restart();
+        assert_eq!(harness.engine.status(), EngineStatus::PostInit);
+    }
+
+    #[test]
+    fn init_failure_without_receiver_exits() {
+        let ctx = MockEventLoop::default();
+        let mut engine = Engine::<TestPipeline>::new(Window::default_attributes(), true);
+
+        engine.on_resumed(&ctx);
+        engine.on_window_event(&ctx, WindowEvent::RedrawRequested);
+        assert_eq!(engine.status(), EngineStatus::Stopped);
+        assert!(ctx.exited());
+
+        // Later events do not panic
+        engine.on_window_event(&ctx, WindowEvent::RedrawRequested);
+    }
+}
@@ -4,4 +4,28 @@ use thiserror::Error;
 pub enum Error {
     #[error("Winit event loop error: {0}")]
     WinitEventLoopError(#[from] winit::error::EventLoopError),
+
+    #[error("Winit OS error: {0}")]
+    WinitOsError(#[from] winit::error::OsError),
+
+    #[error("wgpu create surface error: {0}")]
+    CreateSurface(#[from] wgpu::CreateSurfaceError),
+
+    #[error("wgpu request adapter error: no compatible adapter found")]
+    RequestAdapter,
+
+    #[error("wgpu request device error: {0}")]
+    RequestDevice(#[from] wgpu::RequestDeviceError),
+
+    #[error("Session IO error: {0}")]
+    SessionIo(#[from] std::io::Error),
+
+    #[error("Session serialization error: {0}")]
+    SessionSerde(#[from] serde_json::Error),
+
+    #[error("Session version {0} is not supported")]
+    SessionVersion(u32),
+
+    #[error("Session never starts the system pipeline")]
+    SessionNoStart,
 }
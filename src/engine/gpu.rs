@@ -0,0 +1,398 @@
+use std::{
+    future::Future,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::engine::{Error, RenderTarget};
+
+/// Cache of the [`Gpu`] context.
+///
+/// This is kept by the engine and passed to [`crate::engine::SystemPipeline::init`],
+/// so the GPU context is reused across restarts, and only the surface and the
+/// resources depending on the system pipeline arguments have to be recreated.
+#[derive(Clone, Default)]
+pub struct GpuCache(Arc<Mutex<Option<Arc<Gpu>>>>);
+
+impl GpuCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The cached GPU context, if any.
+    pub fn get(&self) -> Option<Arc<Gpu>> {
+        self.0.lock().expect("GPU cache lock").clone()
+    }
+
+    /// Get the cached GPU context, or create one compatible with the target.
+    ///
+    /// The cached GPU context is only reused if it was created with the same
+    /// policy for the same kind of target, and its device is not lost. A
+    /// context for a headless target has the fallback adapter and no
+    /// compatible surface, so it is not given to a window target, and the
+    /// other way round.
+    pub async fn get_or_init(
+        &self,
+        target: &RenderTarget,
+        policy: &AdapterPolicy,
+    ) -> Result<Arc<Gpu>, Error> {
+        match self.get() {
+            Some(gpu)
+                if gpu.policy() == policy
+                    && gpu.is_headless() == target.is_headless()
+                    && !gpu.is_lost() =>
+            {
+                log::debug!("Reusing cached GPU context");
+                return Ok(gpu);
+            }
+            Some(..) => log::debug!(
+                "Adapter policy or target kind changed or device lost, recreating GPU context"
+            ),
+            None => {}
+        }
+
+        let gpu = Arc::new(Gpu::new(target, policy).await?);
+        *self.0.lock().expect("GPU cache lock") = Some(gpu.clone());
+        Ok(gpu)
+    }
+
+    /// Replace the cached GPU context, e.g. with the one recovered by
+    /// [`Gpu::recover`].
+    pub fn set(&self, gpu: Arc<Gpu>) {
+        *self.0.lock().expect("GPU cache lock") = Some(gpu);
+    }
+
+    /// Clear the cache, so the next [`GpuCache::get_or_init`] creates a new
+    /// GPU context.
+    pub fn clear(&self) {
+        *self.0.lock().expect("GPU cache lock") = None;
+    }
+}
+
+#[cfg(test)]
+impl GpuCache {
+    /// A cache with a GPU context for the headless target, [`None`] if there is
+    /// no fallback adapter, e.g. on a machine without a software renderer, so
+    /// the tests needing a GPU are skipped.
+    pub fn headless_for_test(target: &RenderTarget) -> Option<Self> {
+        let cache = Self::new();
+        match futures::executor::block_on(cache.get_or_init(target, &AdapterPolicy::default())) {
+            Ok(..) => Some(cache),
+            Err(e) => {
+                eprintln!("No headless GPU context, skipping: {e}");
+                None
+            }
+        }
+    }
+}
+
+/// The GPU context.
+pub struct Gpu {
+    instance: Arc<wgpu::Instance>,
+    adapter: wgpu::Adapter,
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    pipeline_cache: Option<wgpu::PipelineCache>,
+    policy: AdapterPolicy,
+    option: AdapterOption,
+    is_headless: bool,
+    is_lost: Arc<AtomicBool>,
+}
+
+impl Gpu {
+    /// Create a new GPU context with an adapter compatible with the target,
+    /// trying the options of the policy in order.
+    ///
+    /// Headless targets use the fallback adapter.
+    pub async fn new(target: &RenderTarget, policy: &AdapterPolicy) -> Result<Self, Error> {
+        let mut error = Error::RequestAdapter;
+        for (i, option) in policy.options.iter().enumerate() {
+            log::debug!("Trying adapter option {i}: {option:?}");
+            match Self::with_option(target, policy, option).await {
+                Ok(gpu) => return Ok(gpu),
+                Err(e) => {
+                    log::warn!("Adapter option {i} failed: {e}");
+                    error = e;
+                }
+            }
+        }
+
+        Err(error)
+    }
+
+    /// Recreate the GPU context after the device is lost.
+    ///
+    /// The adapter and the device are requested again from the same instance
+    /// with the option that succeeded, so the surfaces created from the
+    /// instance can be reconfigured with the new device. Like
+    /// [`Gpu::new`], the adapter has to be compatible with the surface if any.
+    pub fn recover(
+        &self,
+        compatible_surface: Option<Arc<wgpu::Surface<'static>>>,
+    ) -> impl Future<Output = Result<Self, Error>> + 'static {
+        let instance = self.instance.clone();
+        let policy = self.policy.clone();
+        let option = self.option;
+        let is_headless = self.is_headless;
+        async move {
+            log::debug!("Recovering GPU context");
+            Self::request(
+                instance,
+                compatible_surface.as_deref(),
+                &policy,
+                option,
+                is_headless,
+            )
+            .await
+        }
+    }
+
+    async fn with_option(
+        target: &RenderTarget,
+        policy: &AdapterPolicy,
+        option: &AdapterOption,
+    ) -> Result<Self, Error> {
+        let option = AdapterOption {
+            force_fallback_adapter: option.force_fallback_adapter || target.is_headless(),
+            ..*option
+        };
+
+        log::debug!("Creating wgpu instance");
+        let instance = Arc::new(wgpu::Instance::new(wgpu::InstanceDescriptor {
+            backends: option.backends,
+            ..Default::default()
+        }));
+
+        // The surface is only needed to find a compatible adapter, but a canvas
+        // can only have one kind of context, so it is not created for WebGPU,
+        // which does not need it, or a failed WebGPU option would prevent WebGL
+        let needs_surface =
+            !cfg!(target_arch = "wasm32") || option.backends.contains(wgpu::Backends::GL);
+        let surface = target
+            .window()
+            .filter(|_| needs_surface)
+            .map(|window| {
+                log::debug!("Creating compatible window surface");
+                instance.create_surface(window.clone())
+            })
+            .transpose()?;
+
+        Self::request(
+            instance,
+            surface.as_ref(),
+            policy,
+            option,
+            target.is_headless(),
+        )
+        .await
+    }
+
+    /// Request an adapter and a device from the instance.
+    async fn request(
+        instance: Arc<wgpu::Instance>,
+        compatible_surface: Option<&wgpu::Surface<'_>>,
+        policy: &AdapterPolicy,
+        option: AdapterOption,
+        is_headless: bool,
+    ) -> Result<Self, Error> {
+        log::debug!("Requesting adapter");
+        let adapter = instance
+            .request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference: option.power_preference,
+                compatible_surface,
+                force_fallback_adapter: option.force_fallback_adapter,
+            })
+            .await
+            .ok_or(Error::RequestAdapter)?;
+
+        log::debug!("Requesting device");
+        let (device, queue) = adapter
+            .request_device(
+                &wgpu::DeviceDescriptor {
+                    label: Some("Device"),
+                    required_features: adapter.features()
+                        & (wgpu::Features::PIPELINE_CACHE
+                            | wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES),
+                    required_limits: option.limits.limits(&adapter),
+                    memory_hints: wgpu::MemoryHints::default(),
+                },
+                None,
+            )
+            .await?;
+
+        // The callback is also called when the device is dropped
+        let is_lost = Arc::new(AtomicBool::new(false));
+        device.set_device_lost_callback({
+            let is_lost = is_lost.clone();
+            move |reason, message| match reason {
+                wgpu::DeviceLostReason::Unknown => {
+                    log::error!("GPU device lost: {message}");
+                    is_lost.store(true, Ordering::Release);
+                }
+                reason => log::debug!("GPU device lost callback: {reason:?}"),
+            }
+        });
+
+        let pipeline_cache = device
+            .features()
+            .contains(wgpu::Features::PIPELINE_CACHE)
+            .then(|| {
+                log::debug!("Creating pipeline cache");
+
+                // SAFETY: No cache data is provided, so there is nothing to validate
+                unsafe {
+                    device.create_pipeline_cache(&wgpu::PipelineCacheDescriptor {
+                        label: Some("Pipeline Cache"),
+                        data: None,
+                        fallback: true,
+                    })
+                }
+            });
+
+        log::info!("GPU context initialized: {:?}", adapter.get_info());
+
+        Ok(Self {
+            instance,
+            adapter,
+            device,
+            queue,
+            pipeline_cache,
+            policy: policy.clone(),
+            option,
+            is_headless,
+            is_lost,
+        })
+    }
+
+    pub fn instance(&self) -> &wgpu::Instance {
+        &self.instance
+    }
+
+    pub fn adapter(&self) -> &wgpu::Adapter {
+        &self.adapter
+    }
+
+    pub fn device(&self) -> &wgpu::Device {
+        &self.device
+    }
+
+    pub fn queue(&self) -> &wgpu::Queue {
+        &self.queue
+    }
+
+    /// The pipeline cache, if supported by the adapter.
+    pub fn pipeline_cache(&self) -> Option<&wgpu::PipelineCache> {
+        self.pipeline_cache.as_ref()
+    }
+
+    /// The policy the adapter was selected with.
+    pub fn policy(&self) -> &AdapterPolicy {
+        &self.policy
+    }
+
+    /// Whether the GPU context was created for a headless target.
+    pub fn is_headless(&self) -> bool {
+        self.is_headless
+    }
+
+    /// Whether the device is lost, e.g. when the GPU driver is reset, so the
+    /// GPU context has to be recreated by [`Gpu::recover`].
+    pub fn is_lost(&self) -> bool {
+        self.is_lost.load(Ordering::Acquire)
+    }
+
+    /// Mark the device as lost, e.g. when it is out of memory.
+    pub fn set_lost(&self) {
+        self.is_lost.store(true, Ordering::Release);
+    }
+}
+
+/// How [`Gpu`] selects the adapter, the options are tried in order until one
+/// succeeds.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AdapterPolicy {
+    pub options: Vec<AdapterOption>,
+}
+
+impl Default for AdapterPolicy {
+    /// WebGPU then WebGL2 on the web, the primary backends then the fallback
+    /// adapter otherwise.
+    fn default() -> Self {
+        let options = match cfg!(target_arch = "wasm32") {
+            true => vec![
+                AdapterOption::new(wgpu::Backends::BROWSER_WEBGPU),
+                AdapterOption {
+                    limits: AdapterLimits::DownlevelWebgl2,
+                    ..AdapterOption::new(wgpu::Backends::GL)
+                },
+            ],
+            false => vec![
+                AdapterOption::new(wgpu::Backends::PRIMARY),
+                AdapterOption {
+                    force_fallback_adapter: true,
+                    limits: AdapterLimits::Downlevel,
+                    ..AdapterOption::new(wgpu::Backends::all())
+                },
+            ],
+        };
+
+        Self { options }
+    }
+}
+
+/// An option of [`AdapterPolicy`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AdapterOption {
+    /// The backends of the instance.
+    pub backends: wgpu::Backends,
+    pub power_preference: wgpu::PowerPreference,
+    /// Whether to only use the fallback adapter, e.g. a software renderer.
+    pub force_fallback_adapter: bool,
+    /// The limits required of the device.
+    pub limits: AdapterLimits,
+}
+
+impl AdapterOption {
+    /// An option for the backends, with no power preference and the limits of
+    /// the adapter.
+    pub fn new(backends: wgpu::Backends) -> Self {
+        Self {
+            backends,
+            power_preference: wgpu::PowerPreference::None,
+            force_fallback_adapter: false,
+            limits: AdapterLimits::Adapter,
+        }
+    }
+}
+
+/// The limits required of the device by an [`AdapterOption`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum AdapterLimits {
+    /// The limits supported by the adapter.
+    #[default]
+    Adapter,
+    /// [`wgpu::Limits::default`].
+    Default,
+    /// [`wgpu::Limits::downlevel_defaults`].
+    Downlevel,
+    /// [`wgpu::Limits::downlevel_webgl2_defaults`], for WebGL2.
+    DownlevelWebgl2,
+}
+
+impl AdapterLimits {
+    /// The limits, with the texture resolution limits of the adapter.
+    pub fn limits(&self, adapter: &wgpu::Adapter) -> wgpu::Limits {
+        let limits = match self {
+            Self::Adapter => return adapter.limits(),
+            Self::Default => wgpu::Limits::default(),
+            Self::Downlevel => wgpu::Limits::downlevel_defaults(),
+            Self::DownlevelWebgl2 => wgpu::Limits::downlevel_webgl2_defaults(),
+        };
+
+        limits.using_resolution(adapter.limits())
+    }
+}
@@ -0,0 +1,448 @@
+use std::{rc::Rc, sync::mpsc, time::Duration};
+
+use winit::dpi::PhysicalSize;
+use winit_input_helper::WinitInputHelper;
+
+use crate::engine::{
+    queue::{QueueCapacity, SignalQueue},
+    session::{Recorder, Replay, ReplayStep},
+    signal::{InSignalReceiver, LifecycleSignal, OutSignalSender, RenderInfo},
+    timestep, Clock, Error, FixedTimestep, GpuCache, InSignal, Items, Keys, MonotonicClock,
+    NextFrame, OutSignal, RenderTarget, SystemPipeline,
+};
+
+/// Build and run the engine without a window.
+///
+/// The system pipeline renders to an offscreen texture and is updated
+/// frame by frame until the [`Budget`] is exhausted or [`InSignal::Stop`] is
+/// received.
+///
+/// It can also replay a [`crate::engine::session::Session`], see
+/// [`HeadlessRunner::from_replay`].
+pub struct HeadlessRunner<T: SystemPipeline> {
+    size: PhysicalSize<u32>,
+    budget: Budget,
+    system_pipeline_args: T::Args,
+    rx: Option<InSignalReceiver<T>>,
+    tx: Option<mpsc::Sender<OutSignal<T::OutSignal>>>,
+    queue: SignalQueue<T::InSignal>,
+    clock: Rc<dyn Clock>,
+    recorder: Option<Recorder<T>>,
+    replay: Option<Replay<T>>,
+    timestep: Option<FixedTimestep>,
+}
+
+impl<T: SystemPipeline> HeadlessRunner<T> {
+    /// Create a new headless runner with the arguments to pass to
+    /// [`SystemPipeline::init`].
+    pub fn new(system_pipeline_args: T::Args) -> Self {
+        let clock: Rc<dyn Clock> = Rc::new(MonotonicClock::new());
+
+        Self {
+            size: PhysicalSize::new(800, 600),
+            budget: Budget::Frames(1),
+            system_pipeline_args,
+            rx: None,
+            tx: None,
+            queue: SignalQueue::new().with_clock(clock.clone()),
+            clock,
+            recorder: None,
+            replay: None,
+            timestep: None,
+        }
+    }
+
+    /// Create a new headless runner replaying the session.
+    ///
+    /// The size, the arguments and the fixed timestep are the recorded ones,
+    /// and the budget is the number of recorded frames. Incoming signals are
+    /// not handled.
+    pub fn from_replay(mut replay: Replay<T>) -> Result<Self, Error> {
+        let (system_pipeline_args, size) = replay.start().ok_or(Error::SessionNoStart)?;
+        let budget = Budget::Frames(replay.frame_count());
+        let runner = Self::new(system_pipeline_args);
+
+        Ok(Self {
+            size,
+            budget,
+            timestep: replay
+                .timestep()
+                .map(|timestep| FixedTimestep::from(timestep).with_clock(runner.clock.clone())),
+            replay: Some(replay),
+            ..runner
+        })
+    }
+
+    /// Set the size of the offscreen render target.
+    pub fn with_size(mut self, size: PhysicalSize<u32>) -> Self {
+        self.size = size;
+        self
+    }
+
+    /// Set the budget of the run.
+    pub fn with_budget(mut self, budget: Budget) -> Self {
+        self.budget = budget;
+        self
+    }
+
+    /// Set receiver to listen for any events from outside the engine.
+    pub fn with_rx(mut self, rx: InSignalReceiver<T>) -> Self {
+        self.rx = Some(rx);
+        self
+    }
+
+    /// Set sender to send any events to outside the engine.
+    pub fn with_tx(mut self, tx: mpsc::Sender<OutSignal<T::OutSignal>>) -> Self {
+        self.tx = Some(tx);
+        self
+    }
+
+    /// Set the capacity of the custom signal queue, it is unbounded by default.
+    pub fn with_queue_capacity(mut self, capacity: QueueCapacity) -> Self {
+        self.queue = self.queue.with_capacity(capacity);
+        self
+    }
+
+    /// Set the clock of the engine timers and the [`Budget::Duration`], it is
+    /// the [`MonotonicClock`] by default.
+    pub fn with_clock(mut self, clock: Rc<dyn Clock>) -> Self {
+        self.queue = self.queue.with_clock(clock.clone());
+        self.timestep = self
+            .timestep
+            .take()
+            .map(|timestep| timestep.with_clock(clock.clone()));
+        self.recorder = self
+            .recorder
+            .take()
+            .map(|recorder| recorder.with_clock(clock.clone()));
+        self.clock = clock;
+        self
+    }
+
+    /// Set the recorder of the session, it is saved when the run is done.
+    ///
+    /// It measures the frame deltas with the clock of the runner, and records
+    /// the fixed timestep of the runner.
+    pub fn with_recorder(mut self, recorder: Recorder<T>) -> Self {
+        self.recorder = Some(
+            recorder
+                .with_clock(self.clock.clone())
+                .with_timestep(self.timestep.as_ref()),
+        );
+        self
+    }
+
+    /// Set the fixed timestep of [`SystemPipeline::fixed_update`].
+    ///
+    /// It measures the frame delta with the clock of the runner. With a replay,
+    /// it is ignored if it is not the one the session was recorded with.
+    pub fn with_fixed_timestep(mut self, timestep: FixedTimestep) -> Self {
+        if let Some(replay) = &self.replay {
+            if !replay.matches_timestep(Some(&timestep)) {
+                log::warn!(
+                    "Fixed timestep ignored, it is not the one the session was recorded with"
+                );
+                return self;
+            }
+        }
+
+        self.timestep = Some(timestep.with_clock(self.clock.clone()));
+        self.recorder = self
+            .recorder
+            .take()
+            .map(|recorder| recorder.with_timestep(self.timestep.as_ref()));
+        self
+    }
+
+    /// Run the engine, returning the system pipeline when it is done.
+    ///
+    /// Fails if the system pipeline fails to initialize, including on restart.
+    pub async fn run(mut self) -> Result<T, T::Error> {
+        let target = RenderTarget::Headless(self.size);
+
+        log::info!("Starting headless engine");
+        if let Some(recorder) = &mut self.recorder {
+            recorder.start(&self.system_pipeline_args, self.size);
+        }
+
+        let gpu_cache = GpuCache::new();
+        let mut system_pipeline =
+            T::init(target.clone(), gpu_cache.clone(), self.system_pipeline_args).await?;
+        let mut items = Items::<T::OutSignal> {
+            target,
+            gpu_cache,
+            input: WinitInputHelper::new(),
+            keys: Keys::new(),
+            cursor_locked: false,
+            is_replay: false,
+            tx: self.tx.map(OutSignalSender::new),
+            frame_delta: None,
+            fixed_step: None,
+            next_frame: NextFrame::Idle,
+        };
+        send_lifecycle(
+            &items,
+            LifecycleSignal::Initialized(RenderInfo::new(
+                &items.target,
+                &items.gpu_cache,
+                system_pipeline.surface_format(),
+            )),
+        );
+
+        let start = self.clock.now();
+        let mut frame_count = 0;
+
+        while !self
+            .budget
+            .is_exhausted(frame_count, self.clock.now().saturating_sub(start))
+        {
+            // Replay the session instead of the incoming events
+            if let Some(replay) = &mut self.replay {
+                match replay.step(&mut system_pipeline, &mut items, self.timestep.as_mut()) {
+                    ReplayStep::Frame => frame_count += 1,
+                    ReplayStep::Restart { args, size } => {
+                        log::info!("Headless engine restarting for replay");
+                        items.target = RenderTarget::Headless(size);
+                        system_pipeline =
+                            T::init(items.target.clone(), items.gpu_cache.clone(), args).await?;
+                        if let Some(timestep) = &mut self.timestep {
+                            timestep.reset();
+                        }
+                        send_lifecycle(
+                            &items,
+                            LifecycleSignal::Restarted(RenderInfo::new(
+                                &items.target,
+                                &items.gpu_cache,
+                                system_pipeline.surface_format(),
+                            )),
+                        );
+                    }
+                    ReplayStep::Finished => break,
+                }
+                continue;
+            }
+
+            // Handle incoming events
+            if let Some(rx) = &self.rx {
+                for signal in rx.try_iter() {
+                    match signal {
+                        InSignal::Stop => {
+                            log::info!("Headless engine stopping");
+                            if let Some(recorder) = &mut self.recorder {
+                                recorder.stop();
+                            }
+                            save_recording(self.recorder.as_ref());
+                            send_lifecycle(&items, LifecycleSignal::Stopped);
+                            return Ok(system_pipeline);
+                        }
+                        InSignal::Start {
+                            system_pipeline_args,
+                            ..
+                        } => {
+                            log::info!("Headless engine restarting");
+                            if let Some(recorder) = &mut self.recorder {
+                                recorder.stop();
+                                recorder.start(&system_pipeline_args, items.target.size());
+                            }
+                            system_pipeline = T::init(
+                                items.target.clone(),
+                                items.gpu_cache.clone(),
+                                system_pipeline_args,
+                            )
+                            .await?;
+                            if let Some(timestep) = &mut self.timestep {
+                                timestep.reset();
+                            }
+                            send_lifecycle(
+                                &items,
+                                LifecycleSignal::Restarted(RenderInfo::new(
+                                    &items.target,
+                                    &items.gpu_cache,
+                                    system_pipeline.surface_format(),
+                                )),
+                            );
+                        }
+                        InSignal::Pause | InSignal::Resume => {
+                            log::warn!("Headless engine cannot be paused")
+                        }
+                        InSignal::Custom { signal, queue } => self.queue.push(signal, queue),
+                        InSignal::Query { id, query } => {
+                            let result = system_pipeline.query(&mut items, query);
+                            send(&items, OutSignal::from_query_result(id, result));
+                        }
+                    }
+                }
+            }
+
+            // Flush queued signals once per frame
+            for signal in self.queue.drain() {
+                if let Some(recorder) = &mut self.recorder {
+                    recorder.signal(&signal);
+                }
+                system_pipeline.in_signal(&mut items, signal);
+            }
+
+            // Call system pipeline `fixed_update` and `update`
+            items.frame_delta = self.recorder.as_mut().map(Recorder::frame);
+            timestep::update(&mut system_pipeline, &mut items, self.timestep.as_mut());
+            if let Some(recorder) = &mut self.recorder {
+                recorder.end_frame(&items);
+            }
+
+            items.input.end_step();
+            items.input.new_events();
+            items.keys.end_frame();
+
+            frame_count += 1;
+        }
+
+        log::info!("Headless engine finished after {frame_count} frames");
+        save_recording(self.recorder.as_ref());
+        send_lifecycle(&items, LifecycleSignal::Stopped);
+
+        Ok(system_pipeline)
+    }
+}
+
+/// Save the session recorded by the headless engine.
+fn save_recording<T: SystemPipeline>(recorder: Option<&Recorder<T>>) {
+    if let Some(recorder) = recorder {
+        if let Err(e) = recorder.save() {
+            log::error!("Headless engine failed to save session: {e}");
+        }
+    }
+}
+
+/// Send a [`LifecycleSignal`] to outside the headless engine.
+fn send_lifecycle<U>(items: &Items<U>, signal: LifecycleSignal) {
+    send(items, OutSignal::Lifecycle(signal));
+}
+
+/// Send an [`OutSignal`] to outside the headless engine.
+fn send<U>(items: &Items<U>, signal: OutSignal<U>) {
+    if let Some(tx) = &items.tx {
+        if tx.send(signal).is_err() {
+            log::warn!("Outgoing signal receiver dropped");
+        }
+    }
+}
+
+/// The budget of a [`HeadlessRunner`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Budget {
+    /// Run for a number of frames.
+    Frames(u64),
+    /// Run until the duration has elapsed.
+    Duration(Duration),
+}
+
+impl Budget {
+    fn is_exhausted(&self, frame_count: u64, elapsed: Duration) -> bool {
+        match self {
+            Self::Frames(frames) => frame_count >= *frames,
+            Self::Duration(duration) => elapsed >= *duration,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use winit::keyboard::KeyCode;
+
+    use crate::{
+        engine::clock::ManualClock,
+        systems::{self, handlers},
+    };
+
+    use super::*;
+
+    fn camera_position(pipeline: &systems::Pipeline) -> glam::Vec3 {
+        pipeline
+            .scheduler()
+            .handler::<handlers::Camera>()
+            .expect("camera")
+            .model()
+            .position
+    }
+
+    #[test]
+    fn from_replay_uses_recorded_timestep() {
+        let mut recorder =
+            Recorder::<systems::Pipeline>::new().with_timestep(Some(&FixedTimestep::new(30)));
+        recorder.start(&systems::Args::default(), PhysicalSize::new(64, 64));
+        let replay = Replay::new(recorder.into_session()).expect("replay");
+
+        let runner = HeadlessRunner::from_replay(replay)
+            .expect("start")
+            .with_fixed_timestep(FixedTimestep::new(60));
+        assert_eq!(
+            runner.timestep.map(|timestep| timestep.tick()),
+            Some(Duration::from_secs(1) / 30)
+        );
+    }
+
+    #[test]
+    fn replay_matches_recorded_camera() {
+        let size = PhysicalSize::new(64, 64);
+        let target = RenderTarget::Headless(size);
+        let Some(gpu_cache) = GpuCache::headless_for_test(&target) else {
+            return;
+        };
+
+        let args = systems::Args::default();
+        let clock = ManualClock::new();
+        let mut recorder = Recorder::<systems::Pipeline>::new().with_clock(Rc::new(clock.clone()));
+        recorder.start(&args, size);
+
+        let mut pipeline = futures::executor::block_on(systems::Pipeline::init(
+            target.clone(),
+            gpu_cache.clone(),
+            args,
+        ))
+        .expect("init");
+        let mut items = Items::<systems::Signal> {
+            target,
+            gpu_cache,
+            input: WinitInputHelper::new(),
+            keys: Keys::new(),
+            cursor_locked: false,
+            is_replay: false,
+            tx: None,
+            frame_delta: None,
+            fixed_step: None,
+            next_frame: NextFrame::Idle,
+        };
+
+        // Hold forward throughout, only moving once the cursor is locked
+        items.keys.press(KeyCode::KeyW);
+        for frame in 0..8 {
+            items.cursor_locked = frame >= 4;
+
+            clock.advance(Duration::from_millis(16));
+            items.frame_delta = Some(recorder.frame());
+            timestep::update(&mut pipeline, &mut items, None);
+            recorder.end_frame(&items);
+
+            items.input.end_step();
+            items.input.new_events();
+            items.keys.end_frame();
+        }
+
+        let recorded = camera_position(&pipeline);
+        let initial = camera_position(
+            &futures::executor::block_on(systems::Pipeline::init(
+                RenderTarget::Headless(size),
+                GpuCache::new(),
+                systems::Args::default(),
+            ))
+            .expect("init"),
+        );
+        assert_ne!(recorded, initial);
+
+        let replay = Replay::new(recorder.into_session()).expect("replay");
+        let replayed =
+            futures::executor::block_on(HeadlessRunner::from_replay(replay).expect("start").run())
+                .expect("run");
+        assert_eq!(camera_position(&replayed), recorded);
+    }
+}
@@ -1,16 +1,97 @@
-use std::sync::{mpsc, Arc};
+use std::time::Duration;
 
-use winit::window::Window;
 use winit_input_helper::WinitInputHelper;
 
+use crate::engine::{signal::OutSignalSender, FixedStep, GpuCache, Keys, NextFrame, RenderTarget};
+
 /// Items in the engine.
 pub struct Items<T> {
-    /// The window.
-    pub window: Arc<Window>,
+    /// The render target.
+    pub target: RenderTarget,
+
+    /// The GPU context cache.
+    pub gpu_cache: GpuCache,
 
     /// Input helper.
     pub input: WinitInputHelper,
 
+    /// Keyboard state, which is recorded and replayed with a
+    /// [`crate::engine::session::Session`] unlike [`Items::input`].
+    pub keys: Keys,
+
+    /// Whether the cursor is locked, set by the system pipeline.
+    ///
+    /// It is recorded at the end of every frame of a
+    /// [`crate::engine::session::Session`], and set back by the replay before
+    /// the frame.
+    pub cursor_locked: bool,
+
+    /// Whether a [`crate::engine::session::Session`] is being replayed.
+    ///
+    /// The system pipeline should then follow the recorded state, e.g.
+    /// [`Items::cursor_locked`], instead of making changes outside the engine
+    /// such as locking the cursor.
+    pub is_replay: bool,
+
     /// Outgoing signal sender.
-    pub tx: Option<mpsc::Sender<T>>,
+    pub tx: Option<OutSignalSender<T>>,
+
+    /// The delta of the frame, set when recording or replaying a
+    /// [`crate::engine::session::Session`].
+    ///
+    /// The system pipeline should use it over its own timer when set, so the
+    /// replay is deterministic.
+    pub frame_delta: Option<Duration>,
+
+    /// The fixed timestep of the frame, set when the engine has a
+    /// [`crate::engine::FixedTimestep`].
+    pub fixed_step: Option<FixedStep>,
+
+    /// The next frame requested in this frame, taken by the engine after
+    /// [`crate::engine::SystemPipeline::update`].
+    pub next_frame: NextFrame,
+}
+
+impl<T> Items<T> {
+    /// Request the next frame, the sooner request wins if there are several.
+    pub fn request_frame(&mut self, next_frame: NextFrame) {
+        self.next_frame = self.next_frame.sooner(next_frame);
+    }
+}
+
+impl<T: 'static> Items<T> {
+    /// Run `f` with the items of a system pipeline whose outgoing signals are
+    /// converted by `map`, e.g. a member of a [`crate::engine::chain::Chain`].
+    ///
+    /// The input and the render target are shared, so changes made by `f` are
+    /// kept.
+    pub fn with_mapped_tx<U: 'static, R>(
+        &mut self,
+        map: fn(U) -> T,
+        f: impl FnOnce(&mut Items<U>) -> R,
+    ) -> R {
+        let mut items = Items {
+            target: self.target.clone(),
+            gpu_cache: self.gpu_cache.clone(),
+            input: std::mem::take(&mut self.input),
+            keys: std::mem::take(&mut self.keys),
+            cursor_locked: self.cursor_locked,
+            is_replay: self.is_replay,
+            tx: self.tx.as_ref().map(|tx| tx.map(map)),
+            frame_delta: self.frame_delta,
+            fixed_step: self.fixed_step,
+            next_frame: self.next_frame,
+        };
+
+        let result = f(&mut items);
+
+        self.target = items.target;
+        self.input = items.input;
+        self.keys = items.keys;
+        self.cursor_locked = items.cursor_locked;
+        self.frame_delta = items.frame_delta;
+        self.next_frame = items.next_frame;
+
+        result
+    }
 }
@@ -0,0 +1,83 @@
+use std::collections::HashSet;
+
+use serde::{Deserialize, Serialize};
+use winit::{
+    event::WindowEvent,
+    keyboard::{KeyCode, PhysicalKey},
+};
+
+/// Keyboard state of the frame, see [`crate::engine::Items::keys`].
+///
+/// Unlike [`winit_input_helper::WinitInputHelper`], it can be recorded and set
+/// back by a replay, as [`winit::event::KeyEvent`] cannot be constructed
+/// outside of winit.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Keys {
+    #[serde(default)]
+    held: HashSet<KeyCode>,
+    #[serde(default)]
+    pressed: HashSet<KeyCode>,
+    #[serde(default)]
+    released: HashSet<KeyCode>,
+}
+
+impl Keys {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Update the state with the keyboard input, all keys are released when
+    /// the window is unfocused.
+    pub fn window_event(&mut self, event: &WindowEvent) {
+        match event {
+            WindowEvent::KeyboardInput { event, .. } => {
+                let PhysicalKey::Code(key) = event.physical_key else {
+                    return;
+                };
+
+                match (event.state.is_pressed(), event.repeat) {
+                    (true, false) => self.press(key),
+                    (true, true) => {}
+                    (false, _) => self.release(key),
+                }
+            }
+            WindowEvent::Focused(false) => {
+                self.released.extend(self.held.drain());
+            }
+            _ => {}
+        }
+    }
+
+    /// Press the key, e.g. to simulate the input.
+    pub fn press(&mut self, key: KeyCode) {
+        self.held.insert(key);
+        self.pressed.insert(key);
+    }
+
+    /// Release the key, e.g. to simulate the input.
+    pub fn release(&mut self, key: KeyCode) {
+        self.held.remove(&key);
+        self.released.insert(key);
+    }
+
+    /// Clear the keys pressed and released in the frame.
+    pub fn end_frame(&mut self) {
+        self.pressed.clear();
+        self.released.clear();
+    }
+
+    /// Whether the key is held down.
+    pub fn key_held(&self, key: KeyCode) -> bool {
+        self.held.contains(&key)
+    }
+
+    /// Whether the key is pressed in the frame.
+    pub fn key_pressed(&self, key: KeyCode) -> bool {
+        self.pressed.contains(&key)
+    }
+
+    /// Whether the key is released in the frame.
+    pub fn key_released(&self, key: KeyCode) -> bool {
+        self.released.contains(&key)
+    }
+}
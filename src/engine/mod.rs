@@ -1,14 +1,32 @@
+pub mod chain;
+pub mod clock;
+pub mod context;
 mod core;
 mod error;
+pub mod gpu;
+mod headless;
 mod items;
+mod keys;
+pub mod query;
+pub mod queue;
 mod runner;
+pub mod session;
 pub mod signal;
 mod system_pipeline;
+mod target;
+mod timestep;
 pub mod utils;
 
+pub use clock::{Clock, MonotonicClock};
+pub use context::EventLoopContext;
 pub use core::Engine;
 pub use error::Error;
+pub use gpu::{AdapterPolicy, Gpu, GpuCache};
+pub use headless::{Budget, HeadlessRunner};
 pub use items::Items;
+pub use keys::Keys;
 pub use runner::Runner;
-pub use signal::InSignal;
+pub use signal::{InSignal, OutSignal};
 pub use system_pipeline::SystemPipeline;
+pub use target::{NextFrame, RenderTarget};
+pub use timestep::{FixedStep, FixedTimestep};
@@ -0,0 +1,376 @@
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    future::Future,
+    pin::Pin,
+    rc::Rc,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use futures::channel::oneshot;
+
+use crate::engine::{
+    signal::{InSignalSender, LifecycleSignal, QueryError, QueryId},
+    Clock, InSignal, MonotonicClock, OutSignal, SystemPipeline,
+};
+
+/// Pending [`InSignal::Query`] of the host, resolved by the matching replies.
+///
+/// Every [`OutSignal`] received from the engine should be passed to
+/// [`Queries::handle`], and [`Queries::expire`] should be called regularly for
+/// the timeouts to take effect.
+pub struct Queries<U> {
+    inner: Rc<RefCell<QueriesInner<U>>>,
+    clock: Rc<dyn Clock>,
+}
+
+struct QueriesInner<U> {
+    next_id: u64,
+    pending: HashMap<QueryId, PendingQuery<U>>,
+}
+
+struct PendingQuery<U> {
+    tx: oneshot::Sender<Result<U, QueryError>>,
+    deadline: Option<Duration>,
+}
+
+impl<U> Queries<U> {
+    pub fn new() -> Self {
+        Self {
+            inner: Rc::new(RefCell::new(QueriesInner {
+                next_id: 0,
+                pending: HashMap::new(),
+            })),
+            clock: Rc::new(MonotonicClock::new()),
+        }
+    }
+
+    /// Set the clock of the timeouts, it is the [`MonotonicClock`] by default.
+    pub fn with_clock(mut self, clock: Rc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Send a query to the engine, the reply resolves the returned future.
+    ///
+    /// The query fails with [`QueryError::TimedOut`] if it is not replied within
+    /// the timeout.
+    pub fn send<T: SystemPipeline<OutSignal = U>>(
+        &self,
+        tx: &InSignalSender<T>,
+        query: T::Query,
+        timeout: Option<Duration>,
+    ) -> Reply<U> {
+        let (reply_tx, reply_rx) = oneshot::channel();
+        let id = {
+            let mut inner = self.inner.borrow_mut();
+            let id = QueryId(inner.next_id);
+            inner.next_id += 1;
+            inner.pending.insert(
+                id,
+                PendingQuery {
+                    tx: reply_tx,
+                    deadline: timeout.map(|timeout| self.clock.now() + timeout),
+                },
+            );
+            id
+        };
+
+        if tx.send(InSignal::Query { id, query }).is_err() {
+            log::debug!("Engine dropped, query {id:?} failed");
+            self.resolve(id, Err(QueryError::Stopped));
+        }
+
+        Reply {
+            id,
+            rx: reply_rx,
+            queries: self.clone(),
+        }
+    }
+
+    /// Resolve the pending queries with the signal.
+    ///
+    /// Replies are consumed, any other signal is returned to be handled by the
+    /// host. When the engine is stopped, all pending queries fail.
+    pub fn handle(&self, signal: OutSignal<U>) -> Option<OutSignal<U>> {
+        match signal {
+            OutSignal::Reply { id, reply } => {
+                self.resolve(id, Ok(reply));
+                None
+            }
+            OutSignal::QueryFailed { id, error } => {
+                self.resolve(id, Err(error));
+                None
+            }
+            OutSignal::Lifecycle(LifecycleSignal::Stopped | LifecycleSignal::Failed { .. }) => {
+                self.fail_all(QueryError::Stopped);
+                Some(signal)
+            }
+            signal => Some(signal),
+        }
+    }
+
+    /// Fail the queries past their timeout with [`QueryError::TimedOut`].
+    pub fn expire(&self) {
+        let now = self.clock.now();
+        let mut inner = self.inner.borrow_mut();
+        let expired = inner
+            .pending
+            .iter()
+            .filter(|(_, query)| query.deadline.is_some_and(|deadline| deadline <= now))
+            .map(|(id, _)| *id)
+            .collect::<Vec<_>>();
+
+        for id in expired {
+            log::debug!("Query {id:?} timed out");
+            if let Some(query) = inner.pending.remove(&id) {
+                let _ = query.tx.send(Err(QueryError::TimedOut));
+            }
+        }
+    }
+
+    /// Cancel a pending query, it fails with [`QueryError::Cancelled`].
+    pub fn cancel(&self, id: QueryId) {
+        self.resolve(id, Err(QueryError::Cancelled));
+    }
+
+    /// Fail all pending queries.
+    pub fn fail_all(&self, error: QueryError) {
+        let mut inner = self.inner.borrow_mut();
+        for (_, query) in inner.pending.drain() {
+            let _ = query.tx.send(Err(error));
+        }
+    }
+
+    /// Number of pending queries.
+    pub fn len(&self) -> usize {
+        self.inner.borrow().pending.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn resolve(&self, id: QueryId, result: Result<U, QueryError>) {
+        let query = self.inner.borrow_mut().pending.remove(&id);
+        match query {
+            // The receiver is dropped if the query is no longer awaited
+            Some(query) => {
+                let _ = query.tx.send(result);
+            }
+            None => log::debug!("Query {id:?} is not pending, result ignored"),
+        }
+    }
+}
+
+impl<U> Clone for Queries<U> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            clock: self.clock.clone(),
+        }
+    }
+}
+
+impl<U> Default for Queries<U> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Future of the reply to a query sent by [`Queries::send`].
+///
+/// Dropping it cancels the query.
+pub struct Reply<U> {
+    id: QueryId,
+    rx: oneshot::Receiver<Result<U, QueryError>>,
+    queries: Queries<U>,
+}
+
+impl<U> Reply<U> {
+    /// The correlation ID of the query.
+    pub fn id(&self) -> QueryId {
+        self.id
+    }
+
+    /// Cancel the query.
+    pub fn cancel(&self) {
+        self.queries.cancel(self.id);
+    }
+}
+
+impl<U> Future for Reply<U> {
+    type Output = Result<U, QueryError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.rx)
+            .poll(cx)
+            .map(|result| result.unwrap_or(Err(QueryError::Cancelled)))
+    }
+}
+
+impl<U> Drop for Reply<U> {
+    fn drop(&mut self) {
+        let pending = self.queries.inner.borrow_mut().pending.remove(&self.id);
+
+        if pending.is_some() {
+            log::debug!("Query {:?} dropped before its reply", self.id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::FutureExt;
+
+    use super::*;
+    use crate::engine::{
+        clock::ManualClock,
+        signal::{self, InSignalReceiver},
+        GpuCache, RenderTarget,
+    };
+
+    /// System pipeline replying to queries with a number.
+    struct TestPipeline;
+
+    impl SystemPipeline for TestPipeline {
+        type Args = ();
+        type InSignal = ();
+        type OutSignal = u32;
+        type Query = ();
+        type Error = std::convert::Infallible;
+
+        async fn init(_: RenderTarget, _: GpuCache, _: ()) -> Result<Self, Self::Error> {
+            Ok(Self)
+        }
+    }
+
+    fn queries() -> (
+        Queries<u32>,
+        ManualClock,
+        InSignalSender<TestPipeline>,
+        InSignalReceiver<TestPipeline>,
+    ) {
+        let clock = ManualClock::new();
+        let (tx, rx) = signal::channel();
+        let queries = Queries::new().with_clock(Rc::new(clock.clone()));
+        (queries, clock, tx, rx)
+    }
+
+    #[test]
+    fn reply_resolves_query() {
+        let (queries, _, tx, rx) = queries();
+        let reply = queries.send(&tx, (), None);
+        assert!(matches!(
+            rx.try_recv(),
+            Ok(InSignal::Query { id, .. }) if id == reply.id()
+        ));
+
+        let id = reply.id();
+        assert!(queries.handle(OutSignal::Reply { id, reply: 7 }).is_none());
+        assert_eq!(reply.now_or_never(), Some(Ok(7)));
+        assert!(queries.is_empty());
+    }
+
+    #[test]
+    fn expire_times_out_past_deadline() {
+        let (queries, clock, tx, _rx) = queries();
+        let timed = queries.send(&tx, (), Some(Duration::from_secs(1)));
+        let untimed = queries.send(&tx, (), None);
+
+        clock.advance(Duration::from_millis(999));
+        queries.expire();
+        assert_eq!(queries.len(), 2);
+
+        clock.advance(Duration::from_millis(1));
+        queries.expire();
+        assert_eq!(queries.len(), 1);
+        assert_eq!(timed.now_or_never(), Some(Err(QueryError::TimedOut)));
+        assert!(untimed.now_or_never().is_none());
+    }
+
+    #[test]
+    fn cancel_removes_pending() {
+        let (queries, _, tx, _rx) = queries();
+        let reply = queries.send(&tx, (), None);
+
+        reply.cancel();
+        assert!(queries.is_empty());
+        assert_eq!(reply.now_or_never(), Some(Err(QueryError::Cancelled)));
+    }
+
+    #[test]
+    fn dropped_reply_removes_pending() {
+        let (queries, _, tx, _rx) = queries();
+        let reply = queries.send(&tx, (), None);
+        let id = reply.id();
+
+        drop(reply);
+        assert!(queries.is_empty());
+
+        // A late reply to the dropped query is ignored
+        assert!(queries.handle(OutSignal::Reply { id, reply: 7 }).is_none());
+    }
+
+    #[test]
+    fn unknown_and_stale_replies_ignored() {
+        let (queries, _, tx, _rx) = queries();
+        let first = queries.send(&tx, (), None);
+        let second = queries.send(&tx, (), None);
+        let first_id = first.id();
+
+        let unknown = OutSignal::Reply {
+            id: QueryId(u64::MAX),
+            reply: 0,
+        };
+        assert!(queries.handle(unknown).is_none());
+        assert_eq!(queries.len(), 2);
+
+        queries.handle(OutSignal::Reply {
+            id: first_id,
+            reply: 1,
+        });
+        assert_eq!(first.now_or_never(), Some(Ok(1)));
+
+        // A second reply with the same ID does not resolve another query
+        queries.handle(OutSignal::Reply {
+            id: first_id,
+            reply: 2,
+        });
+        assert_eq!(queries.len(), 1);
+        assert!(second.now_or_never().is_none());
+    }
+
+    #[test]
+    fn fail_all_when_engine_stops_or_fails() {
+        for signal in [
+            LifecycleSignal::Stopped,
+            LifecycleSignal::Failed {
+                error: String::new(),
+            },
+        ] {
+            let (queries, _, tx, _rx) = queries();
+            let replies = [
+                queries.send(&tx, (), None),
+                queries.send(&tx, (), Some(Duration::from_secs(1))),
+            ];
+
+            assert!(queries.handle(OutSignal::Lifecycle(signal)).is_some());
+            assert!(queries.is_empty());
+            for reply in replies {
+                assert_eq!(reply.now_or_never(), Some(Err(QueryError::Stopped)));
+            }
+        }
+    }
+
+    #[test]
+    fn send_fails_when_engine_dropped() {
+        let (queries, _, tx, rx) = queries();
+        drop(rx);
+
+        let reply = queries.send(&tx, (), None);
+        assert!(queries.is_empty());
+        assert_eq!(reply.now_or_never(), Some(Err(QueryError::Stopped)));
+    }
+}
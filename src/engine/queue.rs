@@ -0,0 +1,283 @@
+use std::{collections::VecDeque, rc::Rc, time::Duration};
+
+use crate::engine::{
+    signal::{QueueBehavior, QueueMode},
+    Clock, MonotonicClock,
+};
+
+/// Queue of custom signals following their [`QueueBehavior`].
+///
+/// The engine keeps signals in it while stopped, and while running until they
+/// are flushed once per frame, so a flood of signals is collapsed before being
+/// passed to the system pipeline.
+pub struct SignalQueue<U> {
+    signals: VecDeque<QueuedSignal<U>>,
+    capacity: Option<QueueCapacity>,
+    clock: Rc<dyn Clock>,
+}
+
+struct QueuedSignal<U> {
+    signal: U,
+    mode: QueueMode<U>,
+    expires_at: Option<Duration>,
+}
+
+impl<U> SignalQueue<U> {
+    /// Create an unbounded queue.
+    pub fn new() -> Self {
+        Self {
+            signals: VecDeque::new(),
+            capacity: None,
+            clock: Rc::new(MonotonicClock::new()),
+        }
+    }
+
+    /// Set the clock of the expiry, it is the [`MonotonicClock`] by default.
+    pub fn with_clock(mut self, clock: Rc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Set the capacity of the queue.
+    pub fn with_capacity(mut self, capacity: QueueCapacity) -> Self {
+        self.capacity = Some(capacity);
+        self
+    }
+
+    /// Queue a signal.
+    pub fn push(&mut self, signal: U, behavior: QueueBehavior<U>) {
+        self.remove_expired();
+
+        let expires_at = behavior.expiry.map(|expiry| self.clock.now() + expiry);
+
+        let signal = match behavior.mode {
+            QueueMode::Replace(pred) => {
+                self.signals.retain(|queued| !pred(&signal, &queued.signal));
+                signal
+            }
+            QueueMode::Coalesce(merge) => {
+                let mut signal = signal;
+                for queued in self.signals.iter_mut().rev() {
+                    match merge(&mut queued.signal, signal) {
+                        Some(unmerged) => signal = unmerged,
+                        None => {
+                            queued.expires_at = expires_at;
+                            return;
+                        }
+                    }
+                }
+                signal
+            }
+            QueueMode::Ignored | QueueMode::Queued => signal,
+        };
+
+        if let Some(capacity) = self.capacity {
+            if capacity.capacity == 0 {
+                log::debug!("Signal queue has no capacity, dropping signal");
+                return;
+            }
+
+            if self.signals.len() >= capacity.capacity {
+                match capacity.overflow {
+                    QueueOverflow::DropOldest => {
+                        log::debug!("Signal queue full, dropping oldest signal");
+                        self.signals.pop_front();
+                    }
+                    QueueOverflow::DropNewest => {
+                        log::debug!("Signal queue full, dropping newest signal");
+                        return;
+                    }
+                }
+            }
+        }
+
+        self.signals.push_back(QueuedSignal {
+            signal,
+            mode: behavior.mode,
+            expires_at,
+        });
+    }
+
+    /// Take all the signals that have not expired, in order.
+    pub fn drain(&mut self) -> impl Iterator<Item = U> + '_ {
+        self.remove_expired();
+        self.signals.drain(..).map(|queued| queued.signal)
+    }
+
+    /// Remove the signals with [`QueueMode::Ignored`].
+    ///
+    /// This is called when the engine stops.
+    pub fn remove_ignored(&mut self) {
+        self.signals
+            .retain(|queued| !matches!(queued.mode, QueueMode::Ignored));
+    }
+
+    /// The queued signals, in order.
+    pub fn iter(&self) -> impl Iterator<Item = &U> {
+        self.signals.iter().map(|queued| &queued.signal)
+    }
+
+    pub fn len(&self) -> usize {
+        self.signals.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.signals.is_empty()
+    }
+
+    fn remove_expired(&mut self) {
+        let now = self.clock.now();
+        let len = self.signals.len();
+        self.signals
+            .retain(|queued| queued.expires_at.is_none_or(|expires_at| expires_at > now));
+
+        if self.signals.len() < len {
+            log::debug!("Dropped {} expired signals", len - self.signals.len());
+        }
+    }
+}
+
+impl<U> Default for SignalQueue<U> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Capacity of a [`SignalQueue`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QueueCapacity {
+    /// The maximum number of queued signals, every signal is dropped if it is 0.
+    pub capacity: usize,
+    /// What to drop when the queue is full.
+    pub overflow: QueueOverflow,
+}
+
+/// Policy of a full [`SignalQueue`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QueueOverflow {
+    /// Drop the oldest queued signal to make room for the new one.
+    DropOldest,
+    /// Drop the new signal.
+    DropNewest,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::clock::ManualClock;
+
+    fn coalesce_same_parity(queued: &mut u32, signal: u32) -> Option<u32> {
+        match *queued % 2 == signal % 2 {
+            true => {
+                *queued = signal;
+                None
+            }
+            false => Some(signal),
+        }
+    }
+
+    fn queue_with_capacity(capacity: usize, overflow: QueueOverflow) -> SignalQueue<u32> {
+        SignalQueue::new().with_capacity(QueueCapacity { capacity, overflow })
+    }
+
+    #[test]
+    fn drop_oldest_when_full() {
+        let mut queue = queue_with_capacity(2, QueueOverflow::DropOldest);
+        for signal in 0..3 {
+            queue.push(signal, QueueBehavior::queued());
+        }
+
+        assert_eq!(queue.drain().collect::<Vec<_>>(), vec![1, 2]);
+    }
+
+    #[test]
+    fn drop_newest_when_full() {
+        let mut queue = queue_with_capacity(2, QueueOverflow::DropNewest);
+        for signal in 0..3 {
+            queue.push(signal, QueueBehavior::queued());
+        }
+
+        assert_eq!(queue.drain().collect::<Vec<_>>(), vec![0, 1]);
+    }
+
+    #[test]
+    fn zero_capacity_drops_everything() {
+        for overflow in [QueueOverflow::DropOldest, QueueOverflow::DropNewest] {
+            let mut queue = queue_with_capacity(0, overflow);
+            queue.push(0, QueueBehavior::queued());
+            queue.push(1, QueueBehavior::coalesce(coalesce_same_parity));
+
+            assert!(queue.is_empty());
+        }
+    }
+
+    #[test]
+    fn expired_signals_dropped() {
+        let clock = ManualClock::new();
+        let mut queue = SignalQueue::new().with_clock(Rc::new(clock.clone()));
+        queue.push(
+            0,
+            QueueBehavior::queued().with_expiry(Duration::from_secs(1)),
+        );
+        queue.push(
+            1,
+            QueueBehavior::queued().with_expiry(Duration::from_secs(3)),
+        );
+        queue.push(2, QueueBehavior::queued());
+
+        clock.advance(Duration::from_secs(2));
+
+        assert_eq!(queue.drain().collect::<Vec<_>>(), vec![1, 2]);
+    }
+
+    #[test]
+    fn coalesce_refreshes_expiry() {
+        let clock = ManualClock::new();
+        let mut queue = SignalQueue::new().with_clock(Rc::new(clock.clone()));
+        let behavior = || QueueBehavior::coalesce(coalesce_same_parity);
+        queue.push(0, behavior().with_expiry(Duration::from_secs(2)));
+
+        clock.advance(Duration::from_secs(1));
+        queue.push(2, behavior().with_expiry(Duration::from_secs(2)));
+
+        clock.advance(Duration::from_millis(1500));
+
+        assert_eq!(queue.drain().collect::<Vec<_>>(), vec![2]);
+    }
+
+    #[test]
+    fn coalesce_into_newest_mergeable() {
+        let mut queue = SignalQueue::new();
+        let behavior = || QueueBehavior::coalesce(coalesce_same_parity);
+        queue.push(0, behavior());
+        queue.push(1, behavior());
+        queue.push(2, behavior());
+        queue.push(3, behavior());
+        queue.push(5, behavior());
+
+        assert_eq!(queue.drain().collect::<Vec<_>>(), vec![2, 5]);
+    }
+
+    #[test]
+    fn replace_removes_matching() {
+        let mut queue = SignalQueue::new();
+        let behavior = || QueueBehavior::replace(|a: &u32, b: &u32| a % 2 == b % 2);
+        queue.push(0, behavior());
+        queue.push(1, behavior());
+        queue.push(2, behavior());
+
+        assert_eq!(queue.drain().collect::<Vec<_>>(), vec![1, 2]);
+    }
+
+    #[test]
+    fn remove_ignored_keeps_others() {
+        let mut queue = SignalQueue::new();
+        queue.push(0, QueueBehavior::ignored());
+        queue.push(1, QueueBehavior::queued());
+        queue.push(2, QueueBehavior::coalesce(coalesce_same_parity));
+
+        queue.remove_ignored();
+
+        assert_eq!(queue.drain().collect::<Vec<_>>(), vec![1, 2]);
+    }
+}
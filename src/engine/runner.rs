@@ -5,7 +5,11 @@ use winit::{
     window::{Window, WindowAttributes},
 };
 
-use crate::engine::{Engine, Error, InSignal, SystemPipeline};
+use crate::engine::{
+    session::{Recorder, Replay},
+    signal::{InSignalReceiver, Wake},
+    Engine, Error, FixedTimestep, OutSignal, SystemPipeline,
+};
 
 /// Build and run engine.
 pub struct Runner<T, U, V> {
@@ -13,16 +17,18 @@ pub struct Runner<T, U, V> {
     system_pipeline: T,
     rx: U,
     tx: V,
+    timestep: Option<FixedTimestep>,
 }
 
 pub struct NoSystemPipeline;
-pub struct WithSystemPipeline<T: SystemPipeline>(pub T::Args);
+pub struct WithSystemPipeline<T: SystemPipeline>(pub T::Args, pub Option<Recorder<T>>);
+pub struct WithReplay<T: SystemPipeline>(pub Replay<T>);
 
 pub struct NoRx;
-pub struct WithRx<T: SystemPipeline>(pub mpsc::Receiver<InSignal<T>>);
+pub struct WithRx<T: SystemPipeline>(pub InSignalReceiver<T>);
 
 pub struct NoTx;
-pub struct WithTx<T: SystemPipeline>(pub mpsc::Sender<T::OutSignal>);
+pub struct WithTx<T: SystemPipeline>(pub mpsc::Sender<OutSignal<T::OutSignal>>);
 
 impl Runner<NoSystemPipeline, NoRx, NoTx> {
     /// Create a new runner.
@@ -32,6 +38,7 @@ impl Runner<NoSystemPipeline, NoRx, NoTx> {
             system_pipeline: NoSystemPipeline,
             rx: NoRx,
             tx: NoTx,
+            timestep: None,
         }
     }
 }
@@ -53,35 +60,66 @@ impl<T, U, V> Runner<T, U, V> {
     ) -> Runner<WithSystemPipeline<W>, U, V> {
         Runner {
             window_attributes: self.window_attributes,
-            system_pipeline: WithSystemPipeline(args),
+            system_pipeline: WithSystemPipeline(args, None),
             rx: self.rx,
             tx: self.tx,
+            timestep: self.timestep,
+        }
+    }
+
+    /// Replay a session instead of running a system pipeline, see
+    /// [`Engine::with_replay`].
+    pub fn with_replay<W: SystemPipeline>(self, replay: Replay<W>) -> Runner<WithReplay<W>, U, V> {
+        Runner {
+            window_attributes: self.window_attributes,
+            system_pipeline: WithReplay(replay),
+            rx: self.rx,
+            tx: self.tx,
+            timestep: self.timestep,
+        }
+    }
+
+    /// Set the fixed timestep of [`SystemPipeline::fixed_update`], see
+    /// [`Engine::with_fixed_timestep`].
+    pub fn with_fixed_timestep(self, timestep: FixedTimestep) -> Self {
+        Self {
+            timestep: Some(timestep),
+            ..self
         }
     }
 
     /// Set receiver to listen for any events from outside the engine.
-    pub fn with_rx<W: SystemPipeline>(
-        self,
-        rx: mpsc::Receiver<InSignal<W>>,
-    ) -> Runner<T, WithRx<W>, V> {
+    pub fn with_rx<W: SystemPipeline>(self, rx: InSignalReceiver<W>) -> Runner<T, WithRx<W>, V> {
         Runner {
             window_attributes: self.window_attributes,
             system_pipeline: self.system_pipeline,
             rx: WithRx(rx),
             tx: self.tx,
+            timestep: self.timestep,
         }
     }
 
     /// Set sender to send any events to outside the engine.
     pub fn with_tx<W: SystemPipeline>(
         self,
-        tx: mpsc::Sender<W::OutSignal>,
+        tx: mpsc::Sender<OutSignal<W::OutSignal>>,
     ) -> Runner<T, U, WithTx<W>> {
         Runner {
             window_attributes: self.window_attributes,
             system_pipeline: self.system_pipeline,
             rx: self.rx,
             tx: WithTx(tx),
+            timestep: self.timestep,
+        }
+    }
+}
+
+impl<T: SystemPipeline, U, V> Runner<WithSystemPipeline<T>, U, V> {
+    /// Set the recorder of the session, it is saved when the engine exits.
+    pub fn with_recorder(self, recorder: Recorder<T>) -> Self {
+        Self {
+            system_pipeline: WithSystemPipeline(self.system_pipeline.0, Some(recorder)),
+            ..self
         }
     }
 }
@@ -89,10 +127,18 @@ impl<T, U, V> Runner<T, U, V> {
 impl<T: SystemPipeline> Runner<WithSystemPipeline<T>, WithRx<T>, WithTx<T>> {
     /// Run the engine.
     pub fn run(self) -> Result<(), Error> {
-        let event_loop = EventLoop::new()?;
+        let event_loop = EventLoop::<Wake>::with_user_event().build()?;
+        self.rx.0.set_proxy(event_loop.create_proxy());
+
         let mut engine = Engine::<T>::new(self.window_attributes, self.system_pipeline.0)
             .with_rx(self.rx.0)
             .with_tx(self.tx.0);
+        if let Some(recorder) = self.system_pipeline.1 {
+            engine = engine.with_recorder(recorder);
+        }
+        if let Some(timestep) = self.timestep {
+            engine = engine.with_fixed_timestep(timestep);
+        }
 
         log::info!("Starting engine");
         Ok(event_loop.run_app(&mut engine)?)
@@ -102,10 +148,37 @@ impl<T: SystemPipeline> Runner<WithSystemPipeline<T>, WithRx<T>, WithTx<T>> {
 impl<T: SystemPipeline> Runner<WithSystemPipeline<T>, NoRx, NoTx> {
     /// Run the engine.
     pub fn run(self) -> Result<(), Error> {
-        let event_loop = EventLoop::new()?;
+        let event_loop = EventLoop::<Wake>::with_user_event().build()?;
         let mut engine = Engine::<T>::new(self.window_attributes, self.system_pipeline.0);
+        if let Some(recorder) = self.system_pipeline.1 {
+            engine = engine.with_recorder(recorder);
+        }
+        if let Some(timestep) = self.timestep {
+            engine = engine.with_fixed_timestep(timestep);
+        }
 
         log::info!("Starting engine");
         Ok(event_loop.run_app(&mut engine)?)
     }
 }
+
+impl<T: SystemPipeline> Runner<WithReplay<T>, NoRx, NoTx> {
+    /// Run the engine, replaying the session.
+    ///
+    /// The window is created with the recorded size, and the fixed timestep is
+    /// the recorded one.
+    pub fn run(self) -> Result<(), Error> {
+        let mut replay = self.system_pipeline.0;
+        let (args, size) = replay.start().ok_or(Error::SessionNoStart)?;
+
+        let event_loop = EventLoop::<Wake>::with_user_event().build()?;
+        let mut engine = Engine::<T>::new(self.window_attributes.with_inner_size(size), args)
+            .with_replay(replay);
+        if let Some(timestep) = self.timestep {
+            engine = engine.with_fixed_timestep(timestep);
+        }
+
+        log::info!("Starting engine replay");
+        Ok(event_loop.run_app(&mut engine)?)
+    }
+}
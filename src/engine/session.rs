@@ -0,0 +1,835 @@
+use std::{
+    collections::VecDeque,
+    path::{Path, PathBuf},
+    rc::Rc,
+    time::Duration,
+};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use winit::{
+    dpi::{PhysicalPosition, PhysicalSize},
+    event::{
+        DeviceEvent, DeviceId, ElementState, MouseButton, MouseScrollDelta, TouchPhase, WindowEvent,
+    },
+};
+
+use crate::engine::{
+    timestep, Clock, Error, FixedTimestep, Items, Keys, MonotonicClock, SystemPipeline,
+};
+
+/// A recorded session of the engine.
+///
+/// It is recorded by [`Recorder`] and fed back to the system pipeline by
+/// [`Replay`]. Queries are not recorded, as they are not meant to change the
+/// system pipeline.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Session {
+    pub version: u32,
+    /// The fixed timestep the session was recorded with, if any.
+    #[serde(default)]
+    pub timestep: Option<RecordedTimestep>,
+    pub entries: Vec<SessionEntry>,
+}
+
+impl Session {
+    /// The version of the session format.
+    pub const VERSION: u32 = 1;
+
+    pub fn new() -> Self {
+        Self {
+            version: Self::VERSION,
+            timestep: None,
+            entries: Vec::new(),
+        }
+    }
+
+    /// Load a session from a JSON file.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Error> {
+        Self::from_json(&std::fs::read_to_string(path)?)
+    }
+
+    /// Save the session to a JSON file.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), Error> {
+        Ok(std::fs::write(path, self.to_json()?)?)
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, Error> {
+        let session = serde_json::from_str::<Self>(json)?;
+        match session.version {
+            Self::VERSION => Ok(session),
+            version => Err(Error::SessionVersion(version)),
+        }
+    }
+
+    pub fn to_json(&self) -> Result<String, Error> {
+        Ok(serde_json::to_string(self)?)
+    }
+
+    /// Number of [`SessionEntry::Frame`].
+    pub fn frame_count(&self) -> u64 {
+        self.entries
+            .iter()
+            .filter(|entry| matches!(entry, SessionEntry::Frame { .. }))
+            .count() as u64
+    }
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The [`FixedTimestep`] of a [`Session`].
+///
+/// The replay runs with the same one, so the same ticks are run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecordedTimestep {
+    pub tick: Duration,
+    pub max_catch_up: u32,
+}
+
+impl From<&FixedTimestep> for RecordedTimestep {
+    fn from(timestep: &FixedTimestep) -> Self {
+        Self {
+            tick: timestep.tick(),
+            max_catch_up: timestep.max_catch_up(),
+        }
+    }
+}
+
+impl From<RecordedTimestep> for FixedTimestep {
+    fn from(timestep: RecordedTimestep) -> Self {
+        FixedTimestep::from_tick(timestep.tick).with_max_catch_up(timestep.max_catch_up)
+    }
+}
+
+/// Entry of a [`Session`], in the order the engine handled them.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum SessionEntry {
+    /// The system pipeline is initialized with the arguments, rendering to a
+    /// target of the size.
+    Start {
+        args: serde_json::Value,
+        size: (u32, u32),
+    },
+    /// The engine is stopped.
+    Stop,
+    /// The engine is paused.
+    Pause,
+    /// The engine is resumed.
+    Resume,
+    /// A custom signal is passed to [`SystemPipeline::in_signal`].
+    ///
+    /// This is recorded when the signal leaves the queue, so the queue behaviors
+    /// are already applied.
+    Signal(serde_json::Value),
+    /// A window event is passed to the system pipeline.
+    WindowEvent(RecordedWindowEvent),
+    /// A device event is passed to the system pipeline.
+    DeviceEvent(RecordedDeviceEvent),
+    /// [`SystemPipeline::update`] is called with [`Items::frame_delta`],
+    /// [`Items::keys`] and [`Items::cursor_locked`].
+    ///
+    /// The cursor lock is the one at the end of the frame, as the system
+    /// pipeline may change it during the frame.
+    Frame {
+        delta: Duration,
+        #[serde(default)]
+        keys: Keys,
+        #[serde(default)]
+        cursor_locked: bool,
+    },
+}
+
+/// Serializable [`WindowEvent`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum RecordedWindowEvent {
+    Resized {
+        width: u32,
+        height: u32,
+    },
+    CloseRequested,
+    Focused(bool),
+    /// Keyboard input, only recorded for inspection.
+    ///
+    /// It is not replayed, as [`winit::event::KeyEvent`] cannot be constructed
+    /// outside of winit, the key state is replayed with
+    /// [`SessionEntry::Frame`] instead.
+    KeyboardInput {
+        key: String,
+        pressed: bool,
+        repeat: bool,
+    },
+    CursorMoved {
+        x: f64,
+        y: f64,
+    },
+    CursorEntered,
+    CursorLeft,
+    MouseWheel(RecordedScrollDelta),
+    MouseInput {
+        button: RecordedMouseButton,
+        pressed: bool,
+    },
+    RedrawRequested,
+    Occluded(bool),
+}
+
+impl RecordedWindowEvent {
+    /// Record the event, [`None`] if it is not recorded.
+    pub fn new(event: &WindowEvent) -> Option<Self> {
+        Some(match event {
+            WindowEvent::Resized(size) => Self::Resized {
+                width: size.width,
+                height: size.height,
+            },
+            WindowEvent::CloseRequested => Self::CloseRequested,
+            WindowEvent::Focused(focused) => Self::Focused(*focused),
+            WindowEvent::KeyboardInput { event, .. } => Self::KeyboardInput {
+                key: format!("{:?}", event.physical_key),
+                pressed: event.state.is_pressed(),
+                repeat: event.repeat,
+            },
+            WindowEvent::CursorMoved { position, .. } => Self::CursorMoved {
+                x: position.x,
+                y: position.y,
+            },
+            WindowEvent::CursorEntered { .. } => Self::CursorEntered,
+            WindowEvent::CursorLeft { .. } => Self::CursorLeft,
+            WindowEvent::MouseWheel { delta, .. } => Self::MouseWheel(delta.into()),
+            WindowEvent::MouseInput { state, button, .. } => Self::MouseInput {
+                button: (*button).into(),
+                pressed: state.is_pressed(),
+            },
+            WindowEvent::RedrawRequested => Self::RedrawRequested,
+            WindowEvent::Occluded(occluded) => Self::Occluded(*occluded),
+            _ => return None,
+        })
+    }
+
+    /// The event to replay, [`None`] if it cannot be replayed.
+    pub fn to_event(&self) -> Option<WindowEvent> {
+        let device_id = DeviceId::dummy();
+
+        Some(match self {
+            Self::Resized { width, height } => {
+                WindowEvent::Resized(PhysicalSize::new(*width, *height))
+            }
+            Self::CloseRequested => WindowEvent::CloseRequested,
+            Self::Focused(focused) => WindowEvent::Focused(*focused),
+            Self::KeyboardInput { .. } => return None,
+            Self::CursorMoved { x, y } => WindowEvent::CursorMoved {
+                device_id,
+                position: PhysicalPosition::new(*x, *y),
+            },
+            Self::CursorEntered => WindowEvent::CursorEntered { device_id },
+            Self::CursorLeft => WindowEvent::CursorLeft { device_id },
+            Self::MouseWheel(delta) => WindowEvent::MouseWheel {
+                device_id,
+                delta: (*delta).into(),
+                phase: TouchPhase::Moved,
+            },
+            Self::MouseInput { button, pressed } => WindowEvent::MouseInput {
+                device_id,
+                state: element_state(*pressed),
+                button: (*button).into(),
+            },
+            Self::RedrawRequested => WindowEvent::RedrawRequested,
+            Self::Occluded(occluded) => WindowEvent::Occluded(*occluded),
+        })
+    }
+}
+
+/// Serializable [`DeviceEvent`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum RecordedDeviceEvent {
+    MouseMotion {
+        x: f64,
+        y: f64,
+    },
+    MouseWheel(RecordedScrollDelta),
+    Button {
+        button: u32,
+        pressed: bool,
+    },
+    /// Raw keyboard input, only recorded for inspection like
+    /// [`RecordedWindowEvent::KeyboardInput`].
+    Key {
+        key: String,
+        pressed: bool,
+    },
+}
+
+impl RecordedDeviceEvent {
+    /// Record the event, [`None`] if it is not recorded.
+    pub fn new(event: &DeviceEvent) -> Option<Self> {
+        Some(match event {
+            DeviceEvent::MouseMotion { delta: (x, y) } => Self::MouseMotion { x: *x, y: *y },
+            DeviceEvent::MouseWheel { delta } => Self::MouseWheel(delta.into()),
+            DeviceEvent::Button { button, state } => Self::Button {
+                button: *button,
+                pressed: state.is_pressed(),
+            },
+            DeviceEvent::Key(event) => Self::Key {
+                key: format!("{:?}", event.physical_key),
+                pressed: event.state.is_pressed(),
+            },
+            _ => return None,
+        })
+    }
+
+    /// The event to replay, [`None`] if it cannot be replayed.
+    pub fn to_event(&self) -> Option<DeviceEvent> {
+        Some(match self {
+            Self::MouseMotion { x, y } => DeviceEvent::MouseMotion { delta: (*x, *y) },
+            Self::MouseWheel(delta) => DeviceEvent::MouseWheel {
+                delta: (*delta).into(),
+            },
+            Self::Button { button, pressed } => DeviceEvent::Button {
+                button: *button,
+                state: element_state(*pressed),
+            },
+            Self::Key { .. } => return None,
+        })
+    }
+}
+
+/// Serializable [`MouseScrollDelta`].
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum RecordedScrollDelta {
+    Line { x: f32, y: f32 },
+    Pixel { x: f64, y: f64 },
+}
+
+impl From<&MouseScrollDelta> for RecordedScrollDelta {
+    fn from(delta: &MouseScrollDelta) -> Self {
+        match delta {
+            MouseScrollDelta::LineDelta(x, y) => Self::Line { x: *x, y: *y },
+            MouseScrollDelta::PixelDelta(position) => Self::Pixel {
+                x: position.x,
+                y: position.y,
+            },
+        }
+    }
+}
+
+impl From<RecordedScrollDelta> for MouseScrollDelta {
+    fn from(delta: RecordedScrollDelta) -> Self {
+        match delta {
+            RecordedScrollDelta::Line { x, y } => Self::LineDelta(x, y),
+            RecordedScrollDelta::Pixel { x, y } => Self::PixelDelta(PhysicalPosition::new(x, y)),
+        }
+    }
+}
+
+/// Serializable [`MouseButton`].
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum RecordedMouseButton {
+    Left,
+    Right,
+    Middle,
+    Back,
+    Forward,
+    Other(u16),
+}
+
+impl From<MouseButton> for RecordedMouseButton {
+    fn from(button: MouseButton) -> Self {
+        match button {
+            MouseButton::Left => Self::Left,
+            MouseButton::Right => Self::Right,
+            MouseButton::Middle => Self::Middle,
+            MouseButton::Back => Self::Back,
+            MouseButton::Forward => Self::Forward,
+            MouseButton::Other(other) => Self::Other(other),
+        }
+    }
+}
+
+impl From<RecordedMouseButton> for MouseButton {
+    fn from(button: RecordedMouseButton) -> Self {
+        match button {
+            RecordedMouseButton::Left => Self::Left,
+            RecordedMouseButton::Right => Self::Right,
+            RecordedMouseButton::Middle => Self::Middle,
+            RecordedMouseButton::Back => Self::Back,
+            RecordedMouseButton::Forward => Self::Forward,
+            RecordedMouseButton::Other(other) => Self::Other(other),
+        }
+    }
+}
+
+fn element_state(pressed: bool) -> ElementState {
+    match pressed {
+        true => ElementState::Pressed,
+        false => ElementState::Released,
+    }
+}
+
+/// Recorder of a [`Session`].
+///
+/// The engine passes everything that reaches the system pipeline to it, and
+/// measures the frame deltas with it, see [`Items::frame_delta`].
+pub struct Recorder<T: SystemPipeline> {
+    session: Session,
+    path: Option<PathBuf>,
+    serialize_args: fn(&T::Args) -> serde_json::Result<serde_json::Value>,
+    serialize_signal: fn(&T::InSignal) -> serde_json::Result<serde_json::Value>,
+    clock: Rc<dyn Clock>,
+    frame_timer: Duration,
+    paused_timer: Option<Duration>,
+}
+
+impl<T: SystemPipeline> Recorder<T>
+where
+    T::Args: Serialize,
+    T::InSignal: Serialize,
+{
+    pub fn new() -> Self {
+        let clock = Rc::new(MonotonicClock::new());
+
+        Self {
+            session: Session::new(),
+            path: None,
+            serialize_args: |args| serde_json::to_value(args),
+            serialize_signal: |signal| serde_json::to_value(signal),
+            frame_timer: clock.now(),
+            clock,
+            paused_timer: None,
+        }
+    }
+}
+
+impl<T: SystemPipeline> Recorder<T> {
+    /// Set the path to save the session to when the engine exits.
+    pub fn with_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.path = Some(path.into());
+        self
+    }
+
+    /// Set the clock measuring the frame deltas, it is the [`MonotonicClock`]
+    /// by default.
+    pub fn with_clock(mut self, clock: Rc<dyn Clock>) -> Self {
+        self.frame_timer = clock.now();
+        self.paused_timer = None;
+        self.clock = clock;
+        self
+    }
+
+    /// Set the fixed timestep the engine runs with, see [`Session::timestep`].
+    pub fn with_timestep(mut self, timestep: Option<&FixedTimestep>) -> Self {
+        self.session.timestep = timestep.map(RecordedTimestep::from);
+        self
+    }
+
+    /// The recorded session.
+    pub fn session(&self) -> &Session {
+        &self.session
+    }
+
+    /// Take the recorded session.
+    pub fn into_session(self) -> Session {
+        self.session
+    }
+
+    /// Save the session to the path, if any.
+    pub fn save(&self) -> Result<(), Error> {
+        match &self.path {
+            Some(path) => {
+                log::info!("Saving session to {}", path.display());
+                self.session.save(path)
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Record the start of the system pipeline.
+    pub fn start(&mut self, args: &T::Args, size: PhysicalSize<u32>) {
+        match (self.serialize_args)(args) {
+            Ok(args) => self.push(SessionEntry::Start {
+                args,
+                size: (size.width, size.height),
+            }),
+            Err(e) => log::error!("Recorder failed to serialize arguments: {e}"),
+        }
+
+        self.frame_timer = self.clock.now();
+        self.paused_timer = None;
+    }
+
+    pub fn stop(&mut self) {
+        self.push(SessionEntry::Stop);
+    }
+
+    /// Record the pause, the paused duration is excluded from the next frame delta.
+    pub fn pause(&mut self) {
+        self.push(SessionEntry::Pause);
+        self.paused_timer = Some(self.clock.now());
+    }
+
+    pub fn resume(&mut self) {
+        self.push(SessionEntry::Resume);
+        if let Some(paused_timer) = self.paused_timer.take() {
+            self.frame_timer += self.clock.now().saturating_sub(paused_timer);
+        }
+    }
+
+    pub fn signal(&mut self, signal: &T::InSignal) {
+        match (self.serialize_signal)(signal) {
+            Ok(signal) => self.push(SessionEntry::Signal(signal)),
+            Err(e) => log::error!("Recorder failed to serialize signal: {e}"),
+        }
+    }
+
+    pub fn window_event(&mut self, event: &WindowEvent) {
+        if let Some(event) = RecordedWindowEvent::new(event) {
+            self.push(SessionEntry::WindowEvent(event));
+        }
+    }
+
+    pub fn device_event(&mut self, event: &DeviceEvent) {
+        if let Some(event) = RecordedDeviceEvent::new(event) {
+            self.push(SessionEntry::DeviceEvent(event));
+        }
+    }
+
+    /// Measure the delta of a frame, to set as [`Items::frame_delta`].
+    ///
+    /// The frame is recorded by [`Recorder::end_frame`].
+    pub fn frame(&mut self) -> Duration {
+        let now = self.clock.now();
+        let delta = now.saturating_sub(self.frame_timer);
+        self.frame_timer = now;
+
+        delta
+    }
+
+    /// Record the frame after [`SystemPipeline::update`], with the key state
+    /// and the cursor lock of the items.
+    pub fn end_frame<U>(&mut self, items: &Items<U>) {
+        self.push(SessionEntry::Frame {
+            delta: items.frame_delta.unwrap_or_default(),
+            keys: items.keys.clone(),
+            cursor_locked: items.cursor_locked,
+        });
+    }
+
+    fn push(&mut self, entry: SessionEntry) {
+        self.session.entries.push(entry);
+    }
+}
+
+/// Replay of a [`Session`].
+///
+/// [`Replay::start`] gives what to initialize the system pipeline with, then
+/// [`Replay::step`] feeds it the recording frame by frame.
+pub struct Replay<T: SystemPipeline> {
+    timestep: Option<RecordedTimestep>,
+    entries: VecDeque<ReplayEntry<T>>,
+}
+
+enum ReplayEntry<T: SystemPipeline> {
+    Start {
+        args: T::Args,
+        size: PhysicalSize<u32>,
+    },
+    Stop,
+    Pause,
+    Resume,
+    Signal(T::InSignal),
+    WindowEvent(WindowEvent),
+    DeviceEvent(DeviceEvent),
+    Frame {
+        delta: Duration,
+        keys: Keys,
+        cursor_locked: bool,
+    },
+}
+
+impl<T: SystemPipeline> Replay<T>
+where
+    T::Args: DeserializeOwned,
+    T::InSignal: DeserializeOwned,
+{
+    /// Decode the session.
+    ///
+    /// Fails if the session does not start the system pipeline.
+    pub fn new(session: Session) -> Result<Self, Error> {
+        let entries = session
+            .entries
+            .into_iter()
+            .filter_map(|entry| {
+                Some(Ok(match entry {
+                    SessionEntry::Start { args, size } => ReplayEntry::Start {
+                        args: match serde_json::from_value(args) {
+                            Ok(args) => args,
+                            Err(e) => return Some(Err(e.into())),
+                        },
+                        size: PhysicalSize::new(size.0, size.1),
+                    },
+                    SessionEntry::Stop => ReplayEntry::Stop,
+                    SessionEntry::Pause => ReplayEntry::Pause,
+                    SessionEntry::Resume => ReplayEntry::Resume,
+                    SessionEntry::Signal(signal) => match serde_json::from_value(signal) {
+                        Ok(signal) => ReplayEntry::Signal(signal),
+                        Err(e) => return Some(Err(e.into())),
+                    },
+                    SessionEntry::WindowEvent(event) => ReplayEntry::WindowEvent(event.to_event()?),
+                    SessionEntry::DeviceEvent(event) => ReplayEntry::DeviceEvent(event.to_event()?),
+                    SessionEntry::Frame {
+                        delta,
+                        keys,
+                        cursor_locked,
+                    } => ReplayEntry::Frame {
+                        delta,
+                        keys,
+                        cursor_locked,
+                    },
+                }))
+            })
+            .collect::<Result<VecDeque<_>, Error>>()?;
+
+        if !entries
+            .iter()
+            .any(|entry| matches!(entry, ReplayEntry::Start { .. }))
+        {
+            return Err(Error::SessionNoStart);
+        }
+
+        Ok(Self {
+            timestep: session.timestep,
+            entries,
+        })
+    }
+
+    /// Load and decode a session from a JSON file.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Error> {
+        Self::new(Session::load(path)?)
+    }
+}
+
+impl<T: SystemPipeline> Replay<T> {
+    /// Take the next start, skipping anything before it.
+    ///
+    /// This gives the arguments and the render target size to initialize the
+    /// system pipeline with.
+    pub fn start(&mut self) -> Option<(T::Args, PhysicalSize<u32>)> {
+        while let Some(entry) = self.entries.pop_front() {
+            if let ReplayEntry::Start { args, size } = entry {
+                return Some((args, size));
+            }
+        }
+
+        None
+    }
+
+    /// The fixed timestep the session was recorded with, which the replay must
+    /// run with.
+    pub fn timestep(&self) -> Option<RecordedTimestep> {
+        self.timestep
+    }
+
+    /// Whether the fixed timestep is the one the session was recorded with.
+    pub fn matches_timestep(&self, timestep: Option<&FixedTimestep>) -> bool {
+        self.timestep == timestep.map(RecordedTimestep::from)
+    }
+
+    /// Number of frames left.
+    pub fn frame_count(&self) -> u64 {
+        self.entries
+            .iter()
+            .filter(|entry| matches!(entry, ReplayEntry::Frame { .. }))
+            .count() as u64
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Replay the entries up to and including the next frame.
+    ///
+    /// The events, signals and pauses are passed to the system pipeline the same
+    /// way the engine does, and the frame is updated with the recorded delta,
+    /// key state and cursor lock, with [`Items::is_replay`] set.
+    pub fn step(
+        &mut self,
+        system_pipeline: &mut T,
+        items: &mut Items<T::OutSignal>,
+        timestep: Option<&mut FixedTimestep>,
+    ) -> ReplayStep<T> {
+        items.is_replay = true;
+
+        while let Some(entry) = self.entries.pop_front() {
+            match entry {
+                ReplayEntry::Start { args, size } => return ReplayStep::Restart { args, size },
+                ReplayEntry::Stop => {
+                    return match self.start() {
+                        Some((args, size)) => ReplayStep::Restart { args, size },
+                        None => ReplayStep::Finished,
+                    };
+                }
+                ReplayEntry::Pause => system_pipeline.pause(items),
+                ReplayEntry::Resume => system_pipeline.resume(items),
+                ReplayEntry::Signal(signal) => system_pipeline.in_signal(items, signal),
+                ReplayEntry::WindowEvent(event) => {
+                    system_pipeline.window_event(items, &event);
+                    items.input.window_event(&event);
+                }
+                ReplayEntry::DeviceEvent(event) => {
+                    items.input.device_event(&event);
+                    system_pipeline.device_event(items, &event);
+                }
+                ReplayEntry::Frame {
+                    delta,
+                    keys,
+                    cursor_locked,
+                } => {
+                    items.frame_delta = Some(delta);
+                    items.keys = keys;
+                    items.cursor_locked = cursor_locked;
+                    timestep::update(system_pipeline, items, timestep);
+
+                    items.input.end_step();
+                    items.input.new_events();
+                    items.keys.end_frame();
+
+                    return ReplayStep::Frame;
+                }
+            }
+        }
+
+        ReplayStep::Finished
+    }
+}
+
+/// Result of [`Replay::step`].
+pub enum ReplayStep<T: SystemPipeline> {
+    /// A frame is replayed.
+    Frame,
+    /// The engine was stopped and started again, the system pipeline should be
+    /// initialized again with the arguments and the render target size.
+    Restart {
+        args: T::Args,
+        size: PhysicalSize<u32>,
+    },
+    /// The session is finished.
+    Finished,
+}
+
+#[cfg(test)]
+mod tests {
+    use winit::keyboard::KeyCode;
+    use winit_input_helper::WinitInputHelper;
+
+    use crate::{
+        engine::{clock::ManualClock, GpuCache, NextFrame, RenderTarget},
+        systems,
+    };
+
+    use super::*;
+
+    #[test]
+    fn frame_delta_uses_clock_and_excludes_pause() {
+        let clock = ManualClock::new();
+        let mut recorder = Recorder::<systems::Pipeline>::new().with_clock(Rc::new(clock.clone()));
+        recorder.start(&systems::Args::default(), PhysicalSize::new(800, 600));
+
+        clock.advance(Duration::from_millis(16));
+        assert_eq!(recorder.frame(), Duration::from_millis(16));
+
+        recorder.pause();
+        clock.advance(Duration::from_secs(1));
+        recorder.resume();
+        clock.advance(Duration::from_millis(10));
+        assert_eq!(recorder.frame(), Duration::from_millis(10));
+    }
+
+    #[test]
+    fn frame_keys_round_trip() {
+        let mut session = Session::new();
+        let mut keys = Keys::new();
+        keys.press(KeyCode::KeyW);
+        session.entries.push(SessionEntry::Frame {
+            delta: Duration::from_millis(16),
+            keys: keys.clone(),
+            cursor_locked: true,
+        });
+
+        let session = Session::from_json(&session.to_json().expect("serialize")).expect("load");
+        assert!(matches!(
+            session.entries.as_slice(),
+            [SessionEntry::Frame { keys: loaded, cursor_locked: true, .. }] if *loaded == keys
+        ));
+    }
+
+    #[test]
+    fn frame_without_keys_loads() {
+        let json = r#"{"version":1,"entries":[{"Frame":{"delta":{"secs":0,"nanos":16000000}}}]}"#;
+
+        let session = Session::from_json(json).expect("load");
+        assert_eq!(session.timestep, None);
+        assert!(matches!(
+            session.entries.as_slice(),
+            [SessionEntry::Frame {
+                cursor_locked: false,
+                ..
+            }]
+        ));
+    }
+
+    #[test]
+    fn timestep_round_trip() {
+        let timestep = FixedTimestep::new(30).with_max_catch_up(2);
+        let mut recorder = Recorder::<systems::Pipeline>::new().with_timestep(Some(&timestep));
+        recorder.start(&systems::Args::default(), PhysicalSize::new(800, 600));
+
+        let json = recorder.session().to_json().expect("serialize");
+        let replay = Replay::<systems::Pipeline>::new(Session::from_json(&json).expect("load"))
+            .expect("replay");
+        assert_eq!(
+            replay.timestep(),
+            Some(RecordedTimestep {
+                tick: Duration::from_secs(1) / 30,
+                max_catch_up: 2,
+            })
+        );
+        assert!(replay.matches_timestep(Some(&timestep)));
+        assert!(!replay.matches_timestep(Some(&FixedTimestep::new(60))));
+        assert!(!replay.matches_timestep(None));
+    }
+
+    #[test]
+    fn end_frame_records_items() {
+        let clock = ManualClock::new();
+        let mut recorder = Recorder::<systems::Pipeline>::new().with_clock(Rc::new(clock.clone()));
+        let target = RenderTarget::Headless(PhysicalSize::new(1, 1));
+        let mut items = Items::<()> {
+            target,
+            gpu_cache: GpuCache::new(),
+            input: WinitInputHelper::new(),
+            keys: Keys::new(),
+            cursor_locked: false,
+            is_replay: false,
+            tx: None,
+            frame_delta: None,
+            fixed_step: None,
+            next_frame: NextFrame::Idle,
+        };
+
+        clock.advance(Duration::from_millis(16));
+        items.frame_delta = Some(recorder.frame());
+        items.keys.press(KeyCode::KeyW);
+        items.cursor_locked = true;
+        recorder.end_frame(&items);
+
+        assert!(matches!(
+            recorder.session().entries.as_slice(),
+            [SessionEntry::Frame {
+                delta,
+                keys,
+                cursor_locked: true,
+            }] if *delta == Duration::from_millis(16) && keys.key_held(KeyCode::KeyW)
+        ));
+    }
+}
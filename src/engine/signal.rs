@@ -1,6 +1,89 @@
-use winit::window::WindowAttributes;
+use std::{
+    rc::Rc,
+    sync::{mpsc, Arc, OnceLock},
+    time::Duration,
+};
 
-use crate::engine::SystemPipeline;
+use thiserror::Error;
+use winit::{dpi::PhysicalSize, event_loop::EventLoopProxy, window::WindowAttributes};
+
+use crate::engine::{GpuCache, RenderTarget, SystemPipeline};
+
+/// Create a channel of [`InSignal`] for the engine.
+///
+/// Sending a signal wakes the event loop, so the signal is handled at once
+/// instead of waiting for the next window event.
+pub fn channel<T: SystemPipeline>() -> (InSignalSender<T>, InSignalReceiver<T>) {
+    let (tx, rx) = mpsc::channel();
+    let proxy = Arc::new(OnceLock::new());
+
+    (
+        InSignalSender {
+            tx,
+            proxy: proxy.clone(),
+        },
+        InSignalReceiver { rx, proxy },
+    )
+}
+
+/// User event sent to the event loop to wake the engine for incoming signals.
+#[derive(Debug, Clone, Copy)]
+pub struct Wake;
+
+/// Sending half of the [`InSignal`] channel.
+pub struct InSignalSender<T: SystemPipeline> {
+    tx: mpsc::Sender<InSignal<T>>,
+    proxy: Arc<OnceLock<EventLoopProxy<Wake>>>,
+}
+
+impl<T: SystemPipeline> InSignalSender<T> {
+    /// Send a signal and wake the engine.
+    #[allow(clippy::result_large_err)]
+    pub fn send(&self, signal: InSignal<T>) -> Result<(), mpsc::SendError<InSignal<T>>> {
+        self.tx.send(signal)?;
+
+        // The event loop may not be running yet, the signal is handled once it is
+        if let Some(proxy) = self.proxy.get() {
+            if proxy.send_event(Wake).is_err() {
+                log::debug!("Engine event loop closed, signal is not woken");
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<T: SystemPipeline> Clone for InSignalSender<T> {
+    fn clone(&self) -> Self {
+        Self {
+            tx: self.tx.clone(),
+            proxy: self.proxy.clone(),
+        }
+    }
+}
+
+/// Receiving half of the [`InSignal`] channel.
+pub struct InSignalReceiver<T: SystemPipeline> {
+    rx: mpsc::Receiver<InSignal<T>>,
+    proxy: Arc<OnceLock<EventLoopProxy<Wake>>>,
+}
+
+impl<T: SystemPipeline> InSignalReceiver<T> {
+    /// Set the event loop proxy to wake when a signal is sent.
+    pub fn set_proxy(&self, proxy: EventLoopProxy<Wake>) {
+        if self.proxy.set(proxy).is_err() {
+            log::warn!("Event loop proxy already set");
+        }
+    }
+
+    pub fn try_recv(&self) -> Result<InSignal<T>, mpsc::TryRecvError> {
+        self.rx.try_recv()
+    }
+
+    pub fn try_iter(&self) -> mpsc::TryIter<'_, InSignal<T>> {
+        self.rx.try_iter()
+    }
+}
 
 /// Ingcoming signal passed to the engine.
 ///
@@ -15,6 +98,18 @@ pub enum InSignal<T: SystemPipeline> {
     },
     /// Stop the engine.
     Stop,
+    /// Pause the engine.
+    ///
+    /// Updates and rendering are stopped, but unlike [`InSignal::Stop`], the
+    /// system pipeline and its GPU resources are kept alive.
+    Pause,
+    /// Resume the engine after [`InSignal::Pause`].
+    Resume,
+    /// Query the system pipeline.
+    ///
+    /// The engine replies with [`OutSignal::Reply`] or [`OutSignal::QueryFailed`]
+    /// with the same ID, see [`crate::engine::query::Queries`].
+    Query { id: QueryId, query: T::Query },
     /// Custom signal.
     Custom {
         signal: T::InSignal,
@@ -22,19 +117,240 @@ pub enum InSignal<T: SystemPipeline> {
     },
 }
 
-/// Queue behavior of the [`InSignal`].
+/// Queue behavior of the [`InSignal::Custom`].
 ///
-/// The engine provides a queue for signals when it is stopped,
-/// this enum specifies the behavior of the signal when queued.
+/// The engine queues custom signals while it is stopped, and while it is running
+/// until the next frame, see [`crate::engine::queue::SignalQueue`]. This
+/// specifies the behavior of the signal when queued.
+#[derive(Debug, Clone, Copy)]
+pub struct QueueBehavior<U> {
+    /// How the signal is queued.
+    pub mode: QueueMode<U>,
+    /// The signal is dropped if it is still queued after this duration.
+    pub expiry: Option<Duration>,
+}
+
+impl<U> QueueBehavior<U> {
+    pub const fn new(mode: QueueMode<U>) -> Self {
+        Self { mode, expiry: None }
+    }
+
+    /// See [`QueueMode::Ignored`].
+    pub const fn ignored() -> Self {
+        Self::new(QueueMode::Ignored)
+    }
+
+    /// See [`QueueMode::Replace`].
+    pub const fn replace(pred: fn(&U, &U) -> bool) -> Self {
+        Self::new(QueueMode::Replace(pred))
+    }
+
+    /// See [`QueueMode::Coalesce`].
+    pub const fn coalesce(merge: fn(&mut U, U) -> Option<U>) -> Self {
+        Self::new(QueueMode::Coalesce(merge))
+    }
+
+    /// See [`QueueMode::Queued`].
+    pub const fn queued() -> Self {
+        Self::new(QueueMode::Queued)
+    }
+
+    /// Set the expiry of the signal.
+    pub const fn with_expiry(mut self, expiry: Duration) -> Self {
+        self.expiry = Some(expiry);
+        self
+    }
+}
+
+/// Mode of the [`QueueBehavior`].
 #[derive(Debug, Clone, Copy)]
-pub enum QueueBehavior<U> {
-    /// The signal is ignored.
+pub enum QueueMode<U> {
+    /// The signal is ignored when the engine is stopped.
+    ///
+    /// When the engine is running, it is queued until the next frame.
     Ignored,
     /// All the matching signals are replaced.
     ///
     /// A comparison function is provided to determine if two signals are matching.
     /// The first argument is the new signal, and the second argument is the old signal.
     Replace(fn(&U, &U) -> bool),
+    /// The signal is merged into a queued signal.
+    ///
+    /// A merge function is provided, the first argument is the queued signal,
+    /// and the second argument is the new signal. It returns the new signal back
+    /// if it cannot be merged, in which case the next older signal is tried, and
+    /// the signal is queued if none of them can be merged.
+    Coalesce(fn(&mut U, U) -> Option<U>),
     /// The signal is queued.
     Queued,
 }
+
+/// Sending half of the [`OutSignal`] channel, given to the system pipeline in
+/// [`crate::engine::Items::tx`].
+///
+/// Unlike [`mpsc::Sender`], it can be mapped, so that a member of a
+/// [`crate::engine::chain::Chain`] sends its signals wrapped for the chain.
+pub struct OutSignalSender<U> {
+    send: Rc<dyn Fn(OutSignal<U>) -> Result<(), OutSignalSendError>>,
+}
+
+impl<U: 'static> OutSignalSender<U> {
+    pub fn new(tx: mpsc::Sender<OutSignal<U>>) -> Self {
+        Self {
+            send: Rc::new(move |signal| tx.send(signal).map_err(|_| OutSignalSendError)),
+        }
+    }
+
+    /// Create a sender of signals converted by `f` before being sent.
+    pub fn map<V: 'static>(&self, f: fn(V) -> U) -> OutSignalSender<V> {
+        let send = self.send.clone();
+        OutSignalSender {
+            send: Rc::new(move |signal: OutSignal<V>| send(signal.map(f))),
+        }
+    }
+}
+
+impl<U> OutSignalSender<U> {
+    pub fn send(&self, signal: OutSignal<U>) -> Result<(), OutSignalSendError> {
+        (self.send)(signal)
+    }
+}
+
+impl<U> Clone for OutSignalSender<U> {
+    fn clone(&self) -> Self {
+        Self {
+            send: self.send.clone(),
+        }
+    }
+}
+
+/// Error of [`OutSignalSender::send`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+#[error("outgoing signal receiver dropped")]
+pub struct OutSignalSendError;
+
+/// Outgoing signal sent by the engine.
+#[derive(Debug, Clone)]
+pub enum OutSignal<U> {
+    /// Lifecycle signal of the engine itself.
+    Lifecycle(LifecycleSignal),
+    /// Custom signal.
+    Custom(U),
+    /// Reply to [`InSignal::Query`].
+    Reply { id: QueryId, reply: U },
+    /// [`InSignal::Query`] failed.
+    QueryFailed { id: QueryId, error: QueryError },
+}
+
+impl<U> OutSignal<U> {
+    /// Create the reply to [`InSignal::Query`] from the result of
+    /// [`SystemPipeline::query`].
+    pub fn from_query_result(id: QueryId, result: Result<U, QueryError>) -> Self {
+        match result {
+            Ok(reply) => Self::Reply { id, reply },
+            Err(error) => Self::QueryFailed { id, error },
+        }
+    }
+
+    /// Convert the custom signal and the reply.
+    pub fn map<V>(self, f: impl FnOnce(U) -> V) -> OutSignal<V> {
+        match self {
+            Self::Lifecycle(signal) => OutSignal::Lifecycle(signal),
+            Self::Custom(signal) => OutSignal::Custom(f(signal)),
+            Self::Reply { id, reply } => OutSignal::Reply {
+                id,
+                reply: f(reply),
+            },
+            Self::QueryFailed { id, error } => OutSignal::QueryFailed { id, error },
+        }
+    }
+}
+
+/// Correlation ID of [`InSignal::Query`] and its reply.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct QueryId(pub u64);
+
+/// Error of [`InSignal::Query`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+pub enum QueryError {
+    #[error("query not supported by the system pipeline")]
+    Unsupported,
+
+    #[error("engine stopped")]
+    Stopped,
+
+    #[error("query timed out")]
+    TimedOut,
+
+    #[error("query cancelled")]
+    Cancelled,
+}
+
+/// Lifecycle signal of the engine.
+///
+/// These are sent by the engine itself, alongside [`OutSignal::Custom`].
+#[derive(Debug, Clone)]
+pub enum LifecycleSignal {
+    /// The render target is created, and the system pipeline is initializing.
+    Initializing,
+    /// The system pipeline is initialized for the first time.
+    Initialized(RenderInfo),
+    /// The system pipeline is initialized again after [`LifecycleSignal::Stopped`].
+    Restarted(RenderInfo),
+    /// The engine is paused.
+    Paused,
+    /// The engine is resumed.
+    Resumed,
+    /// The render target is hidden, e.g. occluded or minimized, and rendering
+    /// is suspended.
+    Occluded,
+    /// The render target is visible again after [`LifecycleSignal::Occluded`],
+    /// and rendering is resumed unless the engine is paused.
+    Visible,
+    /// The engine is stopped, the system pipeline is dropped.
+    Stopped,
+    /// The engine failed to start, and is now stopped.
+    Failed { error: String },
+}
+
+/// Information about what the engine renders with.
+#[derive(Debug, Clone)]
+pub struct RenderInfo {
+    /// The adapter, [`None`] if the system pipeline did not create a GPU context.
+    pub adapter: Option<wgpu::AdapterInfo>,
+    /// The limits of the device, [`None`] if the system pipeline did not
+    /// create a GPU context.
+    pub limits: Option<Box<wgpu::Limits>>,
+    /// The surface.
+    pub surface: SurfaceInfo,
+}
+
+impl RenderInfo {
+    pub(crate) fn new(
+        target: &RenderTarget,
+        gpu_cache: &GpuCache,
+        format: Option<wgpu::TextureFormat>,
+    ) -> Self {
+        let gpu = gpu_cache.get();
+        Self {
+            adapter: gpu.as_ref().map(|gpu| gpu.adapter().get_info()),
+            limits: gpu.as_ref().map(|gpu| Box::new(gpu.device().limits())),
+            surface: SurfaceInfo {
+                headless: target.is_headless(),
+                size: target.size(),
+                format,
+            },
+        }
+    }
+}
+
+/// Information about the surface rendered to.
+#[derive(Debug, Clone)]
+pub struct SurfaceInfo {
+    /// Whether it is an offscreen texture instead of a window surface.
+    pub headless: bool,
+    /// The size when the system pipeline is initialized.
+    pub size: PhysicalSize<u32>,
+    /// The format, from [`SystemPipeline::surface_format`].
+    pub format: Option<wgpu::TextureFormat>,
+}
@@ -1,11 +1,6 @@
-use std::sync::Arc;
+use winit::event::{DeviceEvent, WindowEvent};
 
-use winit::{
-    event::{DeviceEvent, WindowEvent},
-    window::Window,
-};
-
-use crate::engine::Items;
+use crate::engine::{signal::QueryError, GpuCache, Items, RenderTarget};
 
 #[allow(unused_variables)]
 /// Trait for the system pipeline that the engine will run.
@@ -16,11 +11,29 @@ pub trait SystemPipeline: Sized + 'static {
     /// Custom incoming signal for [`crate::engine::InSignal::Custom`].
     type InSignal;
 
-    /// Outgoing signal.
-    type OutSignal;
+    /// Outgoing signal for [`crate::engine::OutSignal::Custom`].
+    type OutSignal: 'static;
+
+    /// Query for [`crate::engine::InSignal::Query`].
+    type Query;
 
-    /// Called when the window is just created.
-    async fn init(window: Arc<Window>, args: Self::Args) -> Self;
+    /// Error when initializing.
+    type Error: std::error::Error;
+
+    /// Called when the render target is just created.
+    ///
+    /// For [`RenderTarget::Window`], this is right after the window is created.
+    ///
+    /// The [`GpuCache`] is kept across engine restarts, so the GPU context
+    /// can be reused.
+    ///
+    /// If this fails, the engine is stopped and
+    /// [`crate::engine::signal::LifecycleSignal::Failed`] is sent.
+    async fn init(
+        target: RenderTarget,
+        gpu_cache: GpuCache,
+        args: Self::Args,
+    ) -> Result<Self, Self::Error>;
 
     /// Called when there is a [`winit::event::DeviceEvent`].
     ///
@@ -39,9 +52,44 @@ pub trait SystemPipeline: Sized + 'static {
     /// [`Items::input`] is processed.
     fn update(&mut self, items: &mut Items<Self::OutSignal>) {}
 
+    /// Called at a fixed rate before [`SystemPipeline::update`], when the
+    /// engine has a [`crate::engine::FixedTimestep`].
+    ///
+    /// This is called zero or more times per frame, with [`Items::fixed_step`]
+    /// set to the tick.
+    fn fixed_update(&mut self, items: &mut Items<Self::OutSignal>) {}
+
+    /// Called when the engine is paused by [`crate::engine::InSignal::Pause`].
+    ///
+    /// No [`SystemPipeline::update`] is called until [`SystemPipeline::resume`].
+    fn pause(&mut self, items: &mut Items<Self::OutSignal>) {}
+
+    /// Called when the engine is resumed by [`crate::engine::InSignal::Resume`].
+    fn resume(&mut self, items: &mut Items<Self::OutSignal>) {}
+
+    /// The format of the surface rendered to, if any.
+    ///
+    /// This is reported in [`crate::engine::signal::LifecycleSignal::Initialized`].
+    fn surface_format(&self) -> Option<wgpu::TextureFormat> {
+        None
+    }
+
     /// Called when there is a [`SystemPipeline::InSignal`].
     ///
     /// This is called after [`SystemPipeline::window_event`] and
     /// [`SystemPipeline::update`].
     fn in_signal(&mut self, items: &mut Items<Self::OutSignal>, signal: Self::InSignal) {}
+
+    /// Called when there is a [`SystemPipeline::Query`].
+    ///
+    /// The result is sent as [`crate::engine::OutSignal::Reply`] or
+    /// [`crate::engine::OutSignal::QueryFailed`]. This is also called when the
+    /// engine is paused.
+    fn query(
+        &mut self,
+        items: &mut Items<Self::OutSignal>,
+        query: Self::Query,
+    ) -> Result<Self::OutSignal, QueryError> {
+        Err(QueryError::Unsupported)
+    }
 }
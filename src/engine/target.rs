@@ -0,0 +1,70 @@
+use std::{sync::Arc, time::Duration};
+
+use winit::{dpi::PhysicalSize, window::Window};
+
+/// The target that the system pipeline renders to.
+#[derive(Debug, Clone)]
+pub enum RenderTarget {
+    /// A window, rendered to through a surface.
+    Window(Arc<Window>),
+    /// An offscreen texture with the given size, without any window.
+    Headless(PhysicalSize<u32>),
+}
+
+impl RenderTarget {
+    /// The window, if the target is not headless.
+    pub fn window(&self) -> Option<&Arc<Window>> {
+        match self {
+            Self::Window(window) => Some(window),
+            Self::Headless(..) => None,
+        }
+    }
+
+    pub fn is_headless(&self) -> bool {
+        matches!(self, Self::Headless(..))
+    }
+
+    /// The size of the target in physical pixels.
+    pub fn size(&self) -> PhysicalSize<u32> {
+        match self {
+            Self::Window(window) => window.inner_size(),
+            Self::Headless(size) => *size,
+        }
+    }
+
+    /// Request a redraw of the window.
+    ///
+    /// This does nothing for headless targets, which are driven frame by frame.
+    pub fn request_redraw(&self) {
+        if let Self::Window(window) = self {
+            window.request_redraw();
+        }
+    }
+}
+
+/// When the next frame of the render target is requested, see
+/// [`crate::engine::Items::request_frame`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NextFrame {
+    /// No frame is requested, the engine waits for events.
+    #[default]
+    Idle,
+    /// Redraw after the timeout, without blocking the event loop meanwhile.
+    After(Duration),
+    /// Redraw as soon as possible.
+    Now,
+}
+
+impl NextFrame {
+    /// The sooner of the two requests.
+    pub fn sooner(self, other: Self) -> Self {
+        match (self, other) {
+            (Self::Now, _) | (_, Self::Now) => Self::Now,
+            (Self::After(a), Self::After(b)) => Self::After(a.min(b)),
+            (Self::After(timeout), Self::Idle) | (Self::Idle, Self::After(timeout)) => {
+                Self::After(timeout)
+            }
+            (Self::Idle, Self::Idle) => Self::Idle,
+        }
+    }
+}
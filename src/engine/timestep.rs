@@ -0,0 +1,185 @@
+use std::{rc::Rc, time::Duration};
+
+use crate::engine::{Clock, Items, MonotonicClock, SystemPipeline};
+
+/// Fixed timestep of the simulation.
+///
+/// Every frame, the frame delta is added to an accumulator, and
+/// [`SystemPipeline::fixed_update`] is called once for every whole tick in it,
+/// up to the maximum catch-up count. What is left is the interpolation alpha
+/// in [`FixedStep`].
+///
+/// The frame delta is measured with a monotonic [`Clock`], so changes of the
+/// wall clock do not produce negative or huge frames.
+#[derive(Debug, Clone)]
+pub struct FixedTimestep {
+    tick: Duration,
+    max_catch_up: u32,
+    accumulator: Duration,
+    clock: Rc<dyn Clock>,
+    frame_timer: Option<Duration>,
+}
+
+/// The fixed timestep of the current frame, see [`Items::fixed_step`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FixedStep {
+    /// The duration of a tick.
+    pub tick: Duration,
+
+    /// How far the frame is from the last tick to the next one, in `[0, 1)`.
+    ///
+    /// This is `0` in [`SystemPipeline::fixed_update`].
+    pub alpha: f32,
+}
+
+impl FixedTimestep {
+    /// Default maximum number of ticks run in a frame.
+    pub const DEFAULT_MAX_CATCH_UP: u32 = 5;
+
+    /// Create a fixed timestep with a tick rate in Hz.
+    pub fn new(tick_rate: u32) -> Self {
+        Self::from_tick(Duration::from_secs(1) / tick_rate.max(1))
+    }
+
+    /// Create a fixed timestep with the duration of a tick.
+    pub fn from_tick(tick: Duration) -> Self {
+        Self {
+            tick: tick.max(Duration::from_nanos(1)),
+            max_catch_up: Self::DEFAULT_MAX_CATCH_UP,
+            accumulator: Duration::ZERO,
+            clock: Rc::new(MonotonicClock::new()),
+            frame_timer: None,
+        }
+    }
+
+    /// Set the clock measuring the frame delta, it is the [`MonotonicClock`]
+    /// by default.
+    pub fn with_clock(mut self, clock: Rc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self.frame_timer = None;
+        self
+    }
+
+    /// Set the maximum number of ticks run in a frame.
+    ///
+    /// When a frame is longer than this, the rest of it is dropped, so the
+    /// simulation slows down instead of spiraling.
+    pub fn with_max_catch_up(mut self, max_catch_up: u32) -> Self {
+        self.max_catch_up = max_catch_up.max(1);
+        self
+    }
+
+    pub fn tick(&self) -> Duration {
+        self.tick
+    }
+
+    pub fn max_catch_up(&self) -> u32 {
+        self.max_catch_up
+    }
+
+    /// The interpolation alpha, see [`FixedStep::alpha`].
+    pub fn alpha(&self) -> f32 {
+        self.accumulator.as_secs_f32() / self.tick.as_secs_f32()
+    }
+
+    /// Add the frame delta, returning the number of ticks to run.
+    pub fn advance(&mut self, delta: Duration) -> u32 {
+        self.accumulator += delta;
+
+        let ticks = (self.accumulator.as_nanos() / self.tick.as_nanos()) as u32;
+        self.accumulator -= self.tick * ticks;
+
+        if ticks > self.max_catch_up {
+            log::warn!("Fixed timestep dropped {} ticks", ticks - self.max_catch_up);
+            return self.max_catch_up;
+        }
+
+        ticks
+    }
+
+    /// Clear the accumulator and restart measuring the frame delta, e.g. after
+    /// the engine is resumed.
+    pub fn reset(&mut self) {
+        self.accumulator = Duration::ZERO;
+        self.frame_timer = None;
+    }
+
+    /// Measure the delta since the last frame, zero for the first one.
+    fn measure(&mut self) -> Duration {
+        let now = self.clock.now();
+        let delta = self
+            .frame_timer
+            .map(|frame_timer| now.saturating_sub(frame_timer))
+            .unwrap_or_default();
+        self.frame_timer = Some(now);
+
+        delta
+    }
+
+    fn step(&self) -> FixedStep {
+        FixedStep {
+            tick: self.tick,
+            alpha: self.alpha(),
+        }
+    }
+}
+
+/// Call [`SystemPipeline::fixed_update`] for the ticks of the frame if there is
+/// a fixed timestep, then [`SystemPipeline::update`].
+///
+/// [`Items::frame_delta`] is used as the frame delta when set, so replays run
+/// the same ticks.
+pub(crate) fn update<T: SystemPipeline>(
+    system_pipeline: &mut T,
+    items: &mut Items<T::OutSignal>,
+    timestep: Option<&mut FixedTimestep>,
+) {
+    if let Some(timestep) = timestep {
+        let measured = timestep.measure();
+        let ticks = timestep.advance(items.frame_delta.unwrap_or(measured));
+
+        for _ in 0..ticks {
+            items.fixed_step = Some(FixedStep {
+                tick: timestep.tick,
+                alpha: 0.0,
+            });
+            system_pipeline.fixed_update(items);
+        }
+
+        items.fixed_step = Some(timestep.step());
+    }
+
+    system_pipeline.update(items);
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::engine::clock::ManualClock;
+
+    use super::*;
+
+    #[test]
+    fn measure_uses_clock() {
+        let clock = ManualClock::new();
+        let mut timestep = FixedTimestep::new(60).with_clock(Rc::new(clock.clone()));
+
+        assert_eq!(timestep.measure(), Duration::ZERO);
+
+        clock.advance(Duration::from_millis(20));
+        assert_eq!(timestep.measure(), Duration::from_millis(20));
+
+        timestep.reset();
+        clock.advance(Duration::from_secs(10));
+        assert_eq!(timestep.measure(), Duration::ZERO);
+    }
+
+    #[test]
+    fn advance_keeps_remainder_and_caps_catch_up() {
+        let mut timestep = FixedTimestep::new(10).with_max_catch_up(3);
+
+        assert_eq!(timestep.advance(Duration::from_millis(250)), 2);
+        assert!((timestep.alpha() - 0.5).abs() < 1e-4);
+
+        assert_eq!(timestep.advance(Duration::from_secs(1)), 3);
+    }
+}
@@ -1,3 +1,14 @@
+/// Run a future to completion, spawned on the web, or blocking otherwise.
+pub fn spawn(future: impl std::future::Future<Output = ()> + 'static) {
+    cfg_if::cfg_if! {
+        if #[cfg(target_arch = "wasm32")] {
+            wasm_bindgen_futures::spawn_local(future);
+        } else {
+            futures::executor::block_on(future);
+        }
+    }
+}
+
 #[cfg(target_arch = "wasm32")]
 /// Set a timeout in milliseconds.
 pub fn set_timeout(callback: impl FnMut() + 'static, millis: i32) {
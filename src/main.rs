@@ -14,7 +14,7 @@ pub fn main() {
             mount_to_body(ui::App);
         } else {
             use winit::{
-                dpi::LogicalSize,
+                dpi::{LogicalSize, PhysicalSize},
                 window::Window,
             };
 
@@ -23,16 +23,125 @@ pub fn main() {
             }
             env_logger::init();
 
-            engine::Runner::new()
+            let cli_args = std::env::args().collect::<Vec<_>>();
+            let cli_value = |name: &str| {
+                cli_args
+                    .iter()
+                    .position(|arg| arg == name)
+                    .and_then(|i| cli_args.get(i + 1))
+                    .cloned()
+            };
+
+            // Redraw continuously, limited by the FPS limit, or on demand
+            let redraw_policy = match cli_value("--redraw").map(|policy| policy.parse()) {
+                None => systems::RedrawPolicy::default(),
+                Some(Ok(redraw_policy)) => redraw_policy,
+                Some(Err(..)) => {
+                    log::error!(
+                        "Invalid `--redraw` value, expected one of continuous, limited or on-demand"
+                    );
+                    std::process::exit(2);
+                }
+            };
+
+            // Multisample anti-aliasing with the number of samples per pixel
+            let sample_count = match cli_value("--msaa").map(|value| value.parse::<u32>()) {
+                None => 1,
+                Some(Ok(sample_count @ (1 | 2 | 4 | 8 | 16))) => sample_count,
+                Some(..) => {
+                    log::error!("Invalid `--msaa` value, expected one of 1, 2, 4, 8 or 16");
+                    std::process::exit(2);
+                }
+            };
+
+            // Present without waiting for vsync, e.g. for benchmarking
+            let surface = systems::handlers::SurfacePreferences {
+                present_mode: cli_args
+                    .iter()
+                    .any(|arg| arg == "--no-vsync")
+                    .then_some(wgpu::PresentMode::AutoNoVsync),
+                ..Default::default()
+            };
+
+            let args = systems::Args {
+                fps_limit: systems::FpsLimit::new(60),
+                redraw_policy,
+                surface,
+                sample_count,
+                ..Default::default()
+            };
+
+            // Record the session to a file, or replay a recorded one
+            let recorder = cli_value("--record")
+                .map(|path| engine::session::Recorder::<systems::Pipeline>::new().with_path(path));
+            let replay = cli_value("--replay").map(|path| {
+                match engine::session::Replay::<systems::Pipeline>::load(&path) {
+                    Ok(replay) => replay,
+                    Err(e) => {
+                        log::error!("Failed to load `--replay` session {path}: {e}");
+                        std::process::exit(2);
+                    }
+                }
+            });
+
+            // Simulate at a fixed tick rate in Hz
+            let timestep = match cli_value("--tick-rate").map(|tick_rate| tick_rate.parse::<u32>()) {
+                None => None,
+                Some(Ok(tick_rate @ 1..)) => Some(engine::FixedTimestep::new(tick_rate)),
+                Some(..) => {
+                    log::error!("Invalid `--tick-rate` value, expected a positive integer in Hz");
+                    std::process::exit(2);
+                }
+            };
+
+            // Run without a window, e.g. for batch jobs
+            if cli_args.iter().any(|arg| arg == "--headless") {
+                let runner = match replay {
+                    Some(replay) => match engine::HeadlessRunner::from_replay(replay) {
+                        Ok(runner) => runner,
+                        Err(e) => {
+                            log::error!("Failed to replay `--replay` session: {e}");
+                            std::process::exit(2);
+                        }
+                    },
+                    None => engine::HeadlessRunner::<systems::Pipeline>::new(args)
+                        .with_size(PhysicalSize::new(800, 600))
+                        .with_budget(engine::Budget::Frames(600)),
+                };
+                let runner = match recorder {
+                    Some(recorder) => runner.with_recorder(recorder),
+                    None => runner,
+                };
+                let runner = match timestep {
+                    Some(timestep) => runner.with_fixed_timestep(timestep),
+                    None => runner,
+                };
+
+                futures::executor::block_on(runner.run()).unwrap();
+                return;
+            }
+
+            let runner = engine::Runner::new()
                 .with_window_attributes(Window::default_attributes()
                     .with_title("wgpu")
                     .with_inner_size(LogicalSize::new(800.0, 600.0))
-                )
-                .with_system_pipeline::<systems::Pipeline>(systems::Args {
-                    fps_limit: systems::FpsLimit::new(60),
-                })
-                .run()
-                .unwrap();
+                );
+            let runner = match timestep {
+                Some(timestep) => runner.with_fixed_timestep(timestep),
+                None => runner,
+            };
+
+            if let Some(replay) = replay {
+                runner.with_replay(replay).run().unwrap();
+                return;
+            }
+
+            let runner = runner.with_system_pipeline::<systems::Pipeline>(args);
+            match recorder {
+                Some(recorder) => runner.with_recorder(recorder).run(),
+                None => runner.run(),
+            }
+            .unwrap();
         }
     }
 }
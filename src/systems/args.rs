@@ -1,15 +1,39 @@
 use glam::*;
+use serde::{Deserialize, Serialize};
 
-use crate::systems::{
-    handlers::{PyramidModel, PyramidTransform},
-    RgbColor,
+use crate::{
+    engine::AdapterPolicy,
+    systems::{
+        handlers::{DepthConfig, PyramidModel, PyramidTransform, SurfacePreferences},
+        RgbColor,
+    },
 };
 
 /// The configurations of the system pipeline.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Args {
     pub fps_limit: FpsLimit,
+    #[serde(default)]
+    pub redraw_policy: RedrawPolicy,
     pub clear_color: RgbColor,
+    /// The alpha of the clear color, only used if the window surface is
+    /// composited with alpha, see [`SurfacePreferences::alpha_mode`].
+    #[serde(default = "default_clear_alpha")]
+    pub clear_alpha: f32,
+    /// How the adapter is selected, only used when there is no cached GPU
+    /// context created with the same policy.
+    #[serde(default)]
+    pub adapter: AdapterPolicy,
+    /// The preferred configurations of the window surface.
+    #[serde(default)]
+    pub surface: SurfacePreferences,
+    /// The depth buffer of the display, [`None`] to disable it.
+    #[serde(default = "default_depth")]
+    pub depth: Option<DepthConfig>,
+    /// The number of samples per pixel of the display, `1` to disable
+    /// multisample anti-aliasing.
+    #[serde(default = "default_sample_count")]
+    pub sample_count: u32,
     pub pyramid_transform: PyramidTransform,
     pub pyramid_model: PyramidModel,
 }
@@ -18,15 +42,58 @@ impl Default for Args {
     fn default() -> Self {
         Self {
             fps_limit: FpsLimit::default(),
+            redraw_policy: RedrawPolicy::default(),
             clear_color: RgbColor::GRAY,
+            clear_alpha: default_clear_alpha(),
+            adapter: AdapterPolicy::default(),
+            surface: SurfacePreferences::default(),
+            depth: default_depth(),
+            sample_count: default_sample_count(),
             pyramid_transform: PyramidTransform::default(),
             pyramid_model: PyramidModel::default(),
         }
     }
 }
 
+fn default_clear_alpha() -> f32 {
+    1.0
+}
+
+fn default_depth() -> Option<DepthConfig> {
+    Some(DepthConfig::default())
+}
+
+fn default_sample_count() -> u32 {
+    1
+}
+
+/// When the system pipeline redraws.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Default,
+    Serialize,
+    Deserialize,
+    strum::EnumIs,
+    strum::EnumString,
+)]
+#[strum(serialize_all = "kebab-case")]
+pub enum RedrawPolicy {
+    /// Redraw as soon as possible, ignoring the [`FpsLimit`].
+    Continuous,
+    /// Redraw every frame, limited by the [`FpsLimit`].
+    #[default]
+    Limited,
+    /// Redraw only when a handler requests it, on input or on a signal,
+    /// limited by the [`FpsLimit`].
+    OnDemand,
+}
+
 /// The maximum number of frames per second.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
 pub struct FpsLimit(u32);
 
 impl FpsLimit {
@@ -1,12 +1,36 @@
 use thiserror::Error;
 
-use crate::systems::ColorError;
+use crate::{engine, systems::ColorError};
 
 #[derive(Debug, Error)]
 pub enum Error {
+    #[error("engine error: {0}")]
+    Engine(#[from] engine::Error),
+
+    #[error("display create surface error: {0}")]
+    DisplayCreateSurface(#[from] wgpu::CreateSurfaceError),
+
+    #[error("display surface is not supported by the adapter")]
+    DisplaySurfaceUnsupported,
+
     #[error("display lock cursor error: {0}")]
     DisplayLockCursor(#[from] winit::error::ExternalError),
 
+    #[error("cursor lock requires a window")]
+    CursorLockNoWindow,
+
+    #[error("handler not found: {0}")]
+    HandlerMissing(&'static str),
+
+    #[error("resource not found: {0}")]
+    ResourceMissing(&'static str),
+
+    #[error("scheduler ordering cycle between handlers: {0:?}")]
+    SchedulerCycle(Vec<&'static str>),
+
+    #[error("pyramid side count out of range: {0}")]
+    PyramidSideCount(usize),
+
     #[error("color error: {0}")]
     Color(#[from] ColorError),
 }
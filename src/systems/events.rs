@@ -0,0 +1,268 @@
+use std::{
+    any::{Any, TypeId},
+    collections::{HashMap, VecDeque},
+    fmt::Debug,
+    marker::PhantomData,
+};
+
+/// Event bus between handlers, keyed by the event type.
+///
+/// An event emitted in a stage can be read in the rest of this stage and in
+/// the next one, then it is dropped. An event emitted outside of the stages,
+/// e.g. on a window event, can be read until the end of the next frame. Each
+/// reader keeps a [`Subscriber`], so it reads every event once.
+#[derive(Default)]
+pub struct Events {
+    queues: HashMap<TypeId, Box<dyn AnyQueue>>,
+    stage_count: u64,
+    frame_count: u64,
+    is_in_stage: bool,
+}
+
+/// Read position of a reader of the events of type `E`.
+///
+/// A default subscriber reads the events which are still queued, one created
+/// by [`Events::subscribe`] only reads the events emitted after it.
+pub struct Subscriber<E> {
+    next_id: u64,
+    _marker: PhantomData<fn(E)>,
+}
+
+impl<E> Default for Subscriber<E> {
+    fn default() -> Self {
+        Self {
+            next_id: 0,
+            _marker: PhantomData,
+        }
+    }
+}
+
+struct Queue<E> {
+    name: &'static str,
+    events: VecDeque<QueuedEvent<E>>,
+    next_id: u64,
+}
+
+struct QueuedEvent<E> {
+    id: u64,
+    lifetime: Lifetime,
+    event: E,
+}
+
+/// Until when a [`QueuedEvent`] is kept.
+#[derive(Debug, Clone, Copy)]
+enum Lifetime {
+    /// Until the end of the stage after the one it is emitted in.
+    Stage(u64),
+    /// Until the end of the frame it is emitted before.
+    Frame(u64),
+}
+
+impl Events {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Emit an event.
+    pub fn emit<E: Debug + 'static>(&mut self, event: E) {
+        log::trace!("Event {}: {event:?}", std::any::type_name::<E>());
+
+        let lifetime = match self.is_in_stage {
+            true => Lifetime::Stage(self.stage_count),
+            false => Lifetime::Frame(self.frame_count),
+        };
+        let queue = self.queue_mut::<E>();
+        queue.events.push_back(QueuedEvent {
+            id: queue.next_id,
+            lifetime,
+            event,
+        });
+        queue.next_id += 1;
+    }
+
+    /// Subscribe to the events emitted from now on.
+    pub fn subscribe<E: Debug + 'static>(&mut self) -> Subscriber<E> {
+        Subscriber {
+            next_id: self.queue_mut::<E>().next_id,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Read the events not read by `subscriber` yet.
+    pub fn read<E: 'static>(&self, subscriber: &mut Subscriber<E>) -> impl Iterator<Item = &E> {
+        let queue = self.queue::<E>();
+        let next_id = subscriber.next_id;
+        if let Some(queue) = queue {
+            subscriber.next_id = queue.next_id;
+        }
+
+        queue
+            .into_iter()
+            .flat_map(|queue| queue.events.iter())
+            .filter(move |queued| queued.id >= next_id)
+            .map(|queued| &queued.event)
+    }
+
+    /// Move to the next stage, dropping the events emitted before the previous
+    /// one.
+    pub fn advance(&mut self) {
+        self.stage_count += 1;
+        self.is_in_stage = true;
+        let (stage_count, frame_count) = (self.stage_count - 1, self.frame_count);
+        for queue in self.queues.values_mut() {
+            queue.retain_since(stage_count, frame_count);
+        }
+    }
+
+    /// End the frame, dropping the events emitted outside of the stages before
+    /// it.
+    pub fn end_frame(&mut self) {
+        self.frame_count += 1;
+        self.is_in_stage = false;
+        let frame_count = self.frame_count;
+        for queue in self.queues.values_mut() {
+            queue.retain_since(0, frame_count);
+        }
+    }
+
+    /// The queued events with the type name, for debugging.
+    pub fn queued(&self) -> impl Iterator<Item = (&'static str, &dyn Debug)> {
+        self.queues.values().flat_map(|queue| queue.queued())
+    }
+
+    fn queue<E: 'static>(&self) -> Option<&Queue<E>> {
+        self.queues
+            .get(&TypeId::of::<E>())
+            .and_then(|queue| queue.as_any().downcast_ref())
+    }
+
+    fn queue_mut<E: Debug + 'static>(&mut self) -> &mut Queue<E> {
+        self.queues
+            .entry(TypeId::of::<E>())
+            .or_insert_with(|| {
+                Box::new(Queue::<E> {
+                    name: std::any::type_name::<E>(),
+                    events: VecDeque::new(),
+                    next_id: 0,
+                })
+            })
+            .as_any_mut()
+            .downcast_mut()
+            .expect("event queue of the type")
+    }
+}
+
+impl Debug for Events {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list().entries(self.queued()).finish()
+    }
+}
+
+/// Type erased [`Queue`].
+trait AnyQueue {
+    fn retain_since(&mut self, stage_count: u64, frame_count: u64);
+    fn queued(&self) -> Box<dyn Iterator<Item = (&'static str, &dyn Debug)> + '_>;
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+impl<E: Debug + 'static> AnyQueue for Queue<E> {
+    fn retain_since(&mut self, stage_count: u64, frame_count: u64) {
+        self.events.retain(|queued| match queued.lifetime {
+            Lifetime::Stage(emitted) => emitted >= stage_count,
+            Lifetime::Frame(emitted) => emitted >= frame_count,
+        });
+    }
+
+    fn queued(&self) -> Box<dyn Iterator<Item = (&'static str, &dyn Debug)> + '_> {
+        Box::new(
+            self.events
+                .iter()
+                .map(|queued| (self.name, &queued.event as &dyn Debug)),
+        )
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq)]
+    struct Emitted(u32);
+
+    /// Run a frame of [`crate::systems::Stage::FRAME`], reading the events in
+    /// each stage.
+    fn frame(events: &mut Events, subscriber: &mut Subscriber<Emitted>) -> [Vec<u32>; 4] {
+        let mut read = <[Vec<u32>; 4]>::default();
+        for stage in read.iter_mut() {
+            events.advance();
+            stage.extend(events.read(subscriber).map(|Emitted(i)| *i));
+        }
+        events.end_frame();
+        read
+    }
+
+    #[test]
+    fn event_in_stage_is_read_in_next_stage() {
+        let mut events = Events::new();
+        let mut subscriber = events.subscribe::<Emitted>();
+
+        events.advance();
+        events.emit(Emitted(0));
+        events.advance();
+        assert_eq!(events.read(&mut subscriber).count(), 1);
+
+        events.advance();
+        assert_eq!(events.queued().count(), 0);
+    }
+
+    #[test]
+    fn event_outside_stage_is_read_in_update() {
+        let mut events = Events::new();
+        let mut subscriber = events.subscribe::<Emitted>();
+        frame(&mut events, &mut subscriber);
+
+        // E.g. on a window event between frames
+        events.emit(Emitted(1));
+
+        // `Stage::PreUpdate` then `Stage::Update`
+        events.advance();
+        events.advance();
+        assert_eq!(
+            events.read(&mut subscriber).collect::<Vec<_>>(),
+            [&Emitted(1)]
+        );
+
+        // `Stage::Render` then `Stage::PostRender`
+        events.advance();
+        events.advance();
+        assert_eq!(events.queued().count(), 1);
+
+        events.end_frame();
+        assert_eq!(events.queued().count(), 0);
+    }
+
+    #[test]
+    fn event_outside_stage_is_read_once() {
+        let mut events = Events::new();
+        let mut subscriber = events.subscribe::<Emitted>();
+
+        events.emit(Emitted(2));
+        assert_eq!(
+            frame(&mut events, &mut subscriber),
+            [vec![2], vec![], vec![], vec![]]
+        );
+        assert_eq!(
+            frame(&mut events, &mut subscriber),
+            <[Vec<u32>; 4]>::default()
+        );
+    }
+}
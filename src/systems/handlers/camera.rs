@@ -1,18 +1,32 @@
+use std::sync::Arc;
+
 use glam::*;
 use wgpu::util::DeviceExt;
 use winit::keyboard::KeyCode;
 use winit_input_helper::WinitInputHelper;
 
+use crate::{
+    engine::{Gpu, Keys},
+    systems::{
+        handlers::{CursorLockChanged, DeltaTime, SurfaceResized, TimeScale},
+        Context, Error, Handler, InitContext, Signal, Subscriber,
+    },
+};
+
 /// Handler for the camera.
 pub struct Camera {
     model: CameraModel,
 
     model_buffer: wgpu::Buffer,
 
-    bind_group_layout: wgpu::BindGroupLayout,
-    bind_group: wgpu::BindGroup,
+    binding: CameraBinding,
 
     is_model_dirty: bool,
+
+    // Moved by the input only while the cursor is locked
+    is_cursor_locked: bool,
+    cursor_lock_changed: Subscriber<CursorLockChanged>,
+    surface_resized: Subscriber<SurfaceResized>,
 }
 
 impl Camera {
@@ -56,59 +70,77 @@ impl Camera {
 
             model_buffer,
 
-            bind_group_layout,
-            bind_group,
+            binding: CameraBinding {
+                bind_group_layout: Arc::new(bind_group_layout),
+                bind_group: Arc::new(bind_group),
+            },
 
             is_model_dirty: false,
+
+            is_cursor_locked: false,
+            cursor_lock_changed: Subscriber::default(),
+            surface_resized: Subscriber::default(),
         }
     }
 
     /// Camera bind group layout.
     pub fn bind_group_layout(&self) -> &wgpu::BindGroupLayout {
-        &self.bind_group_layout
+        &self.binding.bind_group_layout
     }
 
     /// Camera bind group.
     ///
     /// A single [`Mat4`] buffer bind group.
     pub fn bind_group(&self) -> &wgpu::BindGroup {
-        &self.bind_group
+        &self.binding.bind_group
+    }
+
+    pub fn binding(&self) -> &CameraBinding {
+        &self.binding
     }
 
     pub fn model(&self) -> &CameraModel {
         &self.model
     }
 
-    pub fn update(&mut self, dt: f32, input: &WinitInputHelper) {
+    pub fn update(&mut self, dt: f32, input: &WinitInputHelper, keys: &Keys) {
+        self.update_movement(dt, keys);
+        self.update_rotation(input);
+    }
+
+    /// Move by the held keys.
+    pub fn update_movement(&mut self, dt: f32, keys: &Keys) {
         let right = self.model.right();
         let forward = (self.model.forward() * (Vec3::ONE - CameraModel::UP)).normalize();
 
         // Movement
-        if input.key_held(KeyCode::KeyW) {
+        if keys.key_held(KeyCode::KeyW) {
             self.model.position += forward * self.model.speed * dt;
             self.is_model_dirty = true;
-        } else if input.key_held(KeyCode::KeyS) {
+        } else if keys.key_held(KeyCode::KeyS) {
             self.model.position -= forward * self.model.speed * dt;
             self.is_model_dirty = true;
         }
 
-        if input.key_held(KeyCode::KeyA) {
+        if keys.key_held(KeyCode::KeyA) {
             self.model.position -= right * self.model.speed * dt;
             self.is_model_dirty = true;
-        } else if input.key_held(KeyCode::KeyD) {
+        } else if keys.key_held(KeyCode::KeyD) {
             self.model.position += right * self.model.speed * dt;
             self.is_model_dirty = true;
         }
 
-        if input.key_held(KeyCode::Space) {
+        if keys.key_held(KeyCode::Space) {
             self.model.position += CameraModel::UP * self.model.speed * dt;
             self.is_model_dirty = true;
-        } else if input.key_held(KeyCode::ShiftLeft) {
+        } else if keys.key_held(KeyCode::ShiftLeft) {
             self.model.position -= CameraModel::UP * self.model.speed * dt;
             self.is_model_dirty = true;
         }
+    }
 
-        // Rotation
+    /// Rotate by the mouse movement of the frame.
+    pub fn update_rotation(&mut self, input: &WinitInputHelper) {
         if input.mouse_diff() != (0.0, 0.0) {
             let pitch_delta = input.mouse_diff().1.to_radians() * self.model.mouse_sensitivity;
             let yaw_delta = input.mouse_diff().0.to_radians() * self.model.mouse_sensitivity;
@@ -121,8 +153,21 @@ impl Camera {
         }
     }
 
-    pub fn render(&mut self, queue: &wgpu::Queue, aspect_ratio: f32, input: &WinitInputHelper) {
-        if self.is_model_dirty || input.window_resized().is_some() {
+    /// Recreate the GPU resources on another device, e.g. after the device is
+    /// lost, keeping the model.
+    pub fn recreate(&mut self, device: &wgpu::Device, aspect_ratio: f32) {
+        let camera = Self::new(device, aspect_ratio, self.model.clone());
+        self.model_buffer = camera.model_buffer;
+        self.binding = camera.binding;
+    }
+
+    /// Mark the model buffer to be written, e.g. when the aspect ratio changed.
+    pub fn resize(&mut self) {
+        self.is_model_dirty = true;
+    }
+
+    pub fn render(&mut self, queue: &wgpu::Queue, aspect_ratio: f32) {
+        if self.is_model_dirty {
             queue.write_buffer(
                 &self.model_buffer,
                 0,
@@ -133,6 +178,86 @@ impl Camera {
     }
 }
 
+impl Handler for Camera {
+    fn init(ctx: &mut InitContext) -> Result<Self, Error> {
+        let camera = CameraBuilder::new()
+            .with_device(ctx.resources.require::<Arc<Gpu>>()?.device())
+            .with_aspect_ratio(ctx.display.aspect_ratio())
+            .build();
+        ctx.resources.insert(camera.binding().clone());
+
+        Ok(camera)
+    }
+
+    /// Recreate the GPU resources, and publish the new [`CameraBinding`].
+    fn device_recovered(&mut self, ctx: &mut Context) {
+        let Some(gpu) = ctx.resources.get::<Arc<Gpu>>().cloned() else {
+            log::warn!("Camera not recovered, GPU missing");
+            return;
+        };
+
+        self.recreate(gpu.device(), ctx.display.aspect_ratio());
+        ctx.resources.insert(self.binding().clone());
+        ctx.request_redraw();
+    }
+
+    fn signal(&mut self, _: &mut Context, signal: &Signal) {
+        if let Signal::CameraPosition(..) = signal {
+            log::warn!("Camera position incoming signal ignored, it is only a reply")
+        }
+    }
+
+    /// Move the camera by the tick scaled by [`TimeScale`] while the cursor is
+    /// locked.
+    fn fixed_update(&mut self, ctx: &mut Context) {
+        if let Some(step) = ctx.items.fixed_step {
+            if self.is_cursor_locked {
+                let TimeScale(scale) = ctx.resources.get().copied().unwrap_or(TimeScale(1.0));
+                self.update_movement(step.tick.as_secs_f32() * scale, &ctx.items.keys);
+            }
+        }
+    }
+
+    /// Move the camera while the cursor is locked, only rotate it if there is
+    /// a fixed timestep.
+    ///
+    /// A redraw is requested while the cursor is locked or the camera changed.
+    fn update(&mut self, ctx: &mut Context) {
+        if let Some(event) = ctx.events.read(&mut self.cursor_lock_changed).last() {
+            self.is_cursor_locked = event.locked;
+        }
+
+        if ctx.events.read(&mut self.surface_resized).count() > 0 {
+            self.resize();
+        }
+
+        if self.is_cursor_locked {
+            match (ctx.items.fixed_step, ctx.resources.get()) {
+                (Some(..), _) => self.update_rotation(&ctx.items.input),
+                (None, Some(DeltaTime(delta))) => {
+                    self.update(*delta, &ctx.items.input, &ctx.items.keys)
+                }
+                (None, None) => {}
+            }
+        }
+
+        if self.is_cursor_locked || self.is_model_dirty {
+            ctx.request_redraw();
+        }
+    }
+
+    fn render(&mut self, ctx: &mut Context, _: &mut wgpu::RenderPass) {
+        self.render(ctx.display.queue(), ctx.display.aspect_ratio());
+    }
+}
+
+/// Camera bind group and its layout, published as a resource by [`Camera`].
+#[derive(Debug, Clone)]
+pub struct CameraBinding {
+    pub bind_group_layout: Arc<wgpu::BindGroupLayout>,
+    pub bind_group: Arc<wgpu::BindGroup>,
+}
+
 #[derive(Debug, Clone)]
 pub struct CameraModel {
     pub position: Vec3,
@@ -8,7 +8,10 @@ use winit::{
 };
 use winit_input_helper::WinitInputHelper;
 
-use crate::systems::Error;
+use crate::{
+    engine::Keys,
+    systems::{Context, Error, Handler, InitContext},
+};
 
 /// Handler for cursor locking.
 pub struct CursorLock {
@@ -77,7 +80,7 @@ impl CursorLock {
         }
     }
 
-    pub fn update(&mut self, input: &mut WinitInputHelper) {
+    pub fn update(&mut self, input: &WinitInputHelper, keys: &Keys) {
         // Focus window
         if !self.is_cursor_locked()
             && self.should_lock_cursor()
@@ -94,7 +97,7 @@ impl CursorLock {
         if self.is_cursor_locked()
             && [KeyCode::Escape, KeyCode::Tab]
                 .into_iter()
-                .any(|k| input.key_pressed(k))
+                .any(|k| keys.key_pressed(k))
         {
             match self.set_cursor_locked(false) {
                 Ok(_) => {}
@@ -104,6 +107,45 @@ impl CursorLock {
     }
 }
 
+impl Handler for CursorLock {
+    fn init(ctx: &mut InitContext) -> Result<Self, Error> {
+        let window = ctx.target.window().ok_or(Error::CursorLockNoWindow)?;
+
+        Ok(CursorLockBuilder::new()
+            .with_window(window.clone())
+            .with_should_lock_cursor(true)
+            .build())
+    }
+
+    fn window_event(&mut self, ctx: &mut Context, event: &WindowEvent) {
+        // The replay sets the recorded lock instead
+        if ctx.items.is_replay {
+            return;
+        }
+
+        self.window_event(event);
+        ctx.items.cursor_locked = self.is_cursor_locked();
+    }
+
+    fn pre_update(&mut self, ctx: &mut Context) {
+        if ctx.items.is_replay {
+            return;
+        }
+
+        self.update(&ctx.items.input, &ctx.items.keys);
+        ctx.items.cursor_locked = self.is_cursor_locked();
+    }
+}
+
+/// Event emitted by the [`crate::systems::Scheduler`] after
+/// [`crate::systems::Stage::PreUpdate`] when [`crate::engine::Items::cursor_locked`]
+/// changed, i.e. when [`CursorLock`] locked or unlocked the cursor, or when the
+/// replay of a session did.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CursorLockChanged {
+    pub locked: bool,
+}
+
 /// Builder of [`CursorLock`].
 pub struct CursorLockBuilder<T> {
     window: T,
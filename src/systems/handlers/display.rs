@@ -1,123 +1,296 @@
-use std::sync::Arc;
+use std::{future::Future, sync::Arc};
 
-use winit::{dpi::PhysicalSize, window::Window};
+use serde::{Deserialize, Serialize};
+use winit::{
+    dpi::{LogicalSize, PhysicalSize},
+    window::Window,
+};
 use winit_input_helper::WinitInputHelper;
 
-use crate::systems::RgbColor;
+use crate::{
+    engine::{self, Gpu, RenderTarget},
+    systems::{Error, RgbColor, Signal},
+};
 
 /// Handler for the display.
 pub struct Display {
-    surface: wgpu::Surface<'static>,
-    queue: wgpu::Queue,
-    device: wgpu::Device,
+    gpu: Arc<Gpu>,
+    target: DisplayTarget,
     config: wgpu::SurfaceConfiguration,
+    surface_preferences: SurfacePreferences,
+    sample_count: u32,
+    multisampled: Option<wgpu::TextureView>,
+    depth: Option<Depth>,
 
     size: PhysicalSize<u32>,
     clear_color: RgbColor,
-
-    // This is needed because surface points to the window
-    #[allow(dead_code)]
-    window: Arc<Window>,
+    clear_alpha: f32,
 }
 
 impl Display {
-    pub async fn new(window: Arc<Window>, clear_color: RgbColor) -> Self {
-        let size = window.inner_size();
-
-        log::debug!("Creating wgpu instance");
-        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
-            backends: match cfg!(target_arch = "wasm32") {
-                true => wgpu::Backends::BROWSER_WEBGPU,
-                false => wgpu::Backends::PRIMARY,
-            },
-            ..Default::default()
-        });
-
-        log::debug!("Creating window surface");
-        let surface = instance.create_surface(window.clone()).unwrap();
-
-        log::debug!("Requesting adapter");
-        let adapter = instance
-            .request_adapter(&wgpu::RequestAdapterOptions {
-                power_preference: wgpu::PowerPreference::None,
-                compatible_surface: Some(&surface),
-                force_fallback_adapter: false,
+    /// Format of the offscreen texture for headless targets.
+    pub const HEADLESS_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+
+    pub fn new(
+        gpu: Arc<Gpu>,
+        target: RenderTarget,
+        clear_color: RgbColor,
+        clear_alpha: f32,
+        surface_preferences: SurfacePreferences,
+        depth: Option<DepthConfig>,
+        sample_count: u32,
+    ) -> Result<Self, Error> {
+        let size = target.size();
+
+        let surface = target
+            .window()
+            .map(|window| {
+                log::debug!("Creating window surface");
+                gpu.instance().create_surface(window.clone())
             })
-            .await
-            .expect("request adapter");
-
-        log::debug!("Requesting device");
-        let (device, queue) = adapter
-            .request_device(
-                &wgpu::DeviceDescriptor {
-                    label: Some("Device"),
-                    required_features: wgpu::Features::empty(),
-                    required_limits: adapter.limits(),
-                    memory_hints: wgpu::MemoryHints::default(),
-                },
-                None,
-            )
-            .await
-            .unwrap();
-
-        let surface_caps = surface.get_capabilities(&adapter);
-        let surface_format = surface_caps
-            .formats
-            .iter()
-            .find(|f| f.is_srgb())
-            .copied()
-            .unwrap_or(surface_caps.formats[0]);
-        let config = wgpu::SurfaceConfiguration {
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
-            format: surface_format,
-            width: size.width.max(1),
-            height: size.height.max(1),
-            present_mode: surface_caps.present_modes[0],
-            alpha_mode: surface_caps.alpha_modes[0],
-            view_formats: vec![],
-            desired_maximum_frame_latency: 2,
+            .transpose()?;
+
+        let (target, config) = match (surface, target) {
+            (Some(surface), RenderTarget::Window(window)) => {
+                let surface_caps = surface.get_capabilities(gpu.adapter());
+                let surface_format = surface_preferences
+                    .supported_format(&surface_caps)
+                    .ok_or(Error::DisplaySurfaceUnsupported)?;
+                let config = wgpu::SurfaceConfiguration {
+                    usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+                    format: surface_format,
+                    width: size.width.max(1),
+                    height: size.height.max(1),
+                    present_mode: surface_preferences.supported_present_mode(&surface_caps),
+                    alpha_mode: surface_preferences.supported_alpha_mode(&surface_caps),
+                    view_formats: vec![],
+                    desired_maximum_frame_latency: 2,
+                };
+
+                log::debug!("Configuring surface");
+                surface.configure(gpu.device(), &config);
+
+                (
+                    DisplayTarget::Surface {
+                        surface: Arc::new(surface),
+                        window,
+                    },
+                    config,
+                )
+            }
+            _ => {
+                let config = wgpu::SurfaceConfiguration {
+                    usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+                    format: Self::HEADLESS_FORMAT,
+                    width: size.width.max(1),
+                    height: size.height.max(1),
+                    present_mode: wgpu::PresentMode::AutoNoVsync,
+                    alpha_mode: wgpu::CompositeAlphaMode::Auto,
+                    view_formats: vec![],
+                    desired_maximum_frame_latency: 2,
+                };
+
+                log::debug!("Creating offscreen texture");
+                let texture = Self::create_offscreen_texture(gpu.device(), &config);
+
+                (DisplayTarget::Texture(texture), config)
+            }
         };
 
-        log::debug!("Configuring surface");
-        surface.configure(&device, &config);
+        let sample_count =
+            Self::supported_sample_count(&gpu, &config, depth.as_ref(), sample_count);
+        let multisampled = Self::create_multisampled_view(gpu.device(), &config, sample_count);
+        let depth = depth.map(|depth| {
+            log::debug!("Creating depth texture");
+            Depth::new(gpu.device(), &config, depth, sample_count)
+        });
 
         log::info!("Display handler initialized");
 
-        Self {
-            surface,
-            device,
-            queue,
+        Ok(Self {
+            gpu,
+            target,
             config,
+            surface_preferences,
+            sample_count,
+            multisampled,
+            depth,
 
             size,
             clear_color,
+            clear_alpha,
+        })
+    }
+
+    /// The window surface, [`None`] if the display is headless.
+    pub fn surface(&self) -> Option<&wgpu::Surface<'static>> {
+        match &self.target {
+            DisplayTarget::Surface { surface, .. } => Some(surface.as_ref()),
+            DisplayTarget::Texture(..) => None,
+        }
+    }
+
+    /// Recreate the GPU context after the device is lost, with an adapter
+    /// compatible with the window surface, see [`Gpu::recover`].
+    pub fn recover_gpu(&self) -> impl Future<Output = Result<Gpu, engine::Error>> + 'static {
+        let surface = match &self.target {
+            DisplayTarget::Surface { surface, .. } => Some(surface.clone()),
+            DisplayTarget::Texture(..) => None,
+        };
+
+        self.gpu.recover(surface)
+    }
 
-            window,
+    /// The offscreen texture, [`None`] if the display is not headless.
+    pub fn texture(&self) -> Option<&wgpu::Texture> {
+        match &self.target {
+            DisplayTarget::Surface { .. } => None,
+            DisplayTarget::Texture(texture) => Some(texture),
         }
     }
 
-    pub fn surface(&self) -> &wgpu::Surface {
-        &self.surface
+    pub fn gpu(&self) -> &Gpu {
+        &self.gpu
     }
 
     pub fn queue(&self) -> &wgpu::Queue {
-        &self.queue
+        self.gpu.queue()
     }
 
     pub fn device(&self) -> &wgpu::Device {
-        &self.device
+        self.gpu.device()
     }
 
     pub fn config(&self) -> &wgpu::SurfaceConfiguration {
         &self.config
     }
 
+    /// Set the present mode of the window surface, reconfiguring it.
+    ///
+    /// It falls back to the first supported present mode if the surface does
+    /// not support it.
+    pub fn set_present_mode(&mut self, present_mode: wgpu::PresentMode) {
+        let DisplayTarget::Surface { surface, .. } = &self.target else {
+            log::warn!("Present mode ignored for headless target");
+            return;
+        };
+
+        self.surface_preferences.present_mode = Some(present_mode);
+        let surface_caps = surface.get_capabilities(self.gpu.adapter());
+        let present_mode = self
+            .surface_preferences
+            .supported_present_mode(&surface_caps);
+        if present_mode == self.config.present_mode {
+            return;
+        }
+
+        log::debug!("Setting present mode to {present_mode:?}");
+        self.config.present_mode = present_mode;
+        surface.configure(self.gpu.device(), &self.config);
+    }
+
+    /// Use the GPU context recovered after the device is lost, reconfiguring
+    /// the surface and recreating the textures.
+    pub fn set_gpu(&mut self, gpu: Arc<Gpu>) -> Result<(), Error> {
+        log::debug!("Setting recovered GPU context");
+        self.gpu = gpu;
+
+        match &mut self.target {
+            DisplayTarget::Surface { surface, .. } => {
+                let surface_caps = surface.get_capabilities(self.gpu.adapter());
+                self.config.format = self
+                    .surface_preferences
+                    .supported_format(&surface_caps)
+                    .ok_or(Error::DisplaySurfaceUnsupported)?;
+                self.config.present_mode = self
+                    .surface_preferences
+                    .supported_present_mode(&surface_caps);
+                self.config.alpha_mode =
+                    self.surface_preferences.supported_alpha_mode(&surface_caps);
+                surface.configure(self.gpu.device(), &self.config);
+            }
+            DisplayTarget::Texture(texture) => {
+                *texture = Self::create_offscreen_texture(self.gpu.device(), &self.config);
+            }
+        }
+
+        self.sample_count = Self::supported_sample_count(
+            &self.gpu,
+            &self.config,
+            self.depth_config(),
+            self.sample_count,
+        );
+        self.recreate_targets();
+
+        Ok(())
+    }
+
+    /// The number of samples per pixel, `1` without multisampling.
+    pub fn sample_count(&self) -> u32 {
+        self.sample_count
+    }
+
+    /// The multisample state of the render pipelines rendering to the display,
+    /// which must match its sample count.
+    pub fn multisample_state(&self) -> wgpu::MultisampleState {
+        wgpu::MultisampleState {
+            count: self.sample_count,
+            mask: !0,
+            alpha_to_coverage_enabled: false,
+        }
+    }
+
+    /// Set the number of samples per pixel, recreating the render targets.
+    ///
+    /// It falls back to `1` if the adapter does not support it.
+    pub fn set_sample_count(&mut self, sample_count: u32) {
+        let sample_count = Self::supported_sample_count(
+            &self.gpu,
+            &self.config,
+            self.depth_config(),
+            sample_count,
+        );
+        if sample_count == self.sample_count {
+            return;
+        }
+
+        log::debug!("Setting sample count to {sample_count}");
+        self.sample_count = sample_count;
+        self.recreate_targets();
+    }
+
+    /// The depth buffer configuration, [`None`] if there is no depth buffer.
+    pub fn depth_config(&self) -> Option<&DepthConfig> {
+        self.depth.as_ref().map(|depth| &depth.config)
+    }
+
+    /// The format of the depth texture, if any.
+    pub fn depth_format(&self) -> Option<wgpu::TextureFormat> {
+        self.depth_config().map(|config| config.format)
+    }
+
+    /// The depth stencil state of the render pipelines rendering to the
+    /// display, which must match its depth buffer.
+    pub fn depth_stencil_state(&self) -> Option<wgpu::DepthStencilState> {
+        self.depth_config().map(DepthConfig::depth_stencil_state)
+    }
+
     pub fn resize(&mut self, size: PhysicalSize<u32>) {
         if size.width > 0 && size.height > 0 {
             self.size = size;
             self.config.width = size.width;
             self.config.height = size.height;
-            self.surface.configure(&self.device, &self.config);
+
+            match &mut self.target {
+                DisplayTarget::Surface { surface, .. } => {
+                    surface.configure(self.gpu.device(), &self.config);
+                }
+                DisplayTarget::Texture(texture) => {
+                    *texture = Self::create_offscreen_texture(self.gpu.device(), &self.config);
+                }
+            }
+
+            self.recreate_targets();
         }
     }
 
@@ -131,37 +304,105 @@ impl Display {
         }
     }
 
-    pub fn render(&mut self, render: impl FnOnce(&mut Display, &mut wgpu::RenderPass)) {
-        let texture = self.surface.get_current_texture().unwrap();
-        let texture_view = texture
-            .texture
-            .create_view(&wgpu::TextureViewDescriptor::default());
+    /// Resize the window on [`Signal::Resize`], set the present mode on
+    /// [`Signal::PresentMode`], and set the sample count on
+    /// [`Signal::SampleCount`].
+    pub fn signal(&mut self, signal: &Signal) {
+        match signal {
+            Signal::Resize(resize) => {
+                log::debug!(
+                    "Resize incoming signal: {} x {}",
+                    resize.width,
+                    resize.height
+                );
+                match &self.target {
+                    DisplayTarget::Surface { window, .. } => {
+                        let _ = window
+                            .request_inner_size(LogicalSize::new(resize.width, resize.height));
+                    }
+                    DisplayTarget::Texture(..) => {
+                        log::warn!("Resize incoming signal ignored for headless target")
+                    }
+                }
+            }
+            Signal::PresentMode(update) => {
+                log::debug!("Present mode incoming signal: {:?}", update.present_mode);
+                self.set_present_mode(update.present_mode);
+            }
+            Signal::SampleCount(update) => {
+                log::debug!("Sample count incoming signal: {}", update.sample_count);
+                self.set_sample_count(update.sample_count);
+            }
+            _ => {}
+        }
+    }
+
+    /// Render a frame, returning whether it is rendered.
+    ///
+    /// The frame is skipped on surface errors, after reconfiguring the surface
+    /// if it is lost or outdated, or marking the device as lost if it is out
+    /// of memory.
+    pub fn render(&self, render: impl FnOnce(&Display, &mut wgpu::RenderPass)) -> bool {
+        let (surface_texture, texture_view) = match &self.target {
+            DisplayTarget::Surface { surface, .. } => {
+                let texture = match surface.get_current_texture() {
+                    Ok(texture) => texture,
+                    Err(e @ (wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated)) => {
+                        log::warn!("Surface error: {e}, reconfiguring the surface");
+                        surface.configure(self.gpu.device(), &self.config);
+                        return false;
+                    }
+                    Err(e @ wgpu::SurfaceError::Timeout) => {
+                        log::warn!("Surface error: {e}, skipping the frame");
+                        return false;
+                    }
+                    Err(e @ wgpu::SurfaceError::OutOfMemory) => {
+                        log::error!("Surface error: {e}, recovering the device");
+                        self.gpu.set_lost();
+                        return false;
+                    }
+                };
+                let texture_view = texture
+                    .texture
+                    .create_view(&wgpu::TextureViewDescriptor::default());
+                (Some(texture), texture_view)
+            }
+            DisplayTarget::Texture(texture) => {
+                let texture_view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+                (None, texture_view)
+            }
+        };
 
         // Create encoder
-        let mut encoder = self
-            .device
-            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
-                label: Some("Render Encoder"),
-            });
+        let mut encoder =
+            self.gpu
+                .device()
+                .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                    label: Some("Render Encoder"),
+                });
 
         // Render pass
         {
             let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
                 label: Some("Render Pass"),
                 color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &texture_view,
-                    resolve_target: None,
+                    view: self.multisampled.as_ref().unwrap_or(&texture_view),
+                    resolve_target: self.multisampled.as_ref().map(|_| &texture_view),
                     ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color {
-                            r: self.clear_color.r() as f64,
-                            g: self.clear_color.g() as f64,
-                            b: self.clear_color.b() as f64,
-                            a: 1.0,
-                        }),
+                        load: wgpu::LoadOp::Clear(self.clear_value()),
                         store: wgpu::StoreOp::Store,
                     },
                 })],
-                depth_stencil_attachment: None,
+                depth_stencil_attachment: self.depth.as_ref().map(|depth| {
+                    wgpu::RenderPassDepthStencilAttachment {
+                        view: &depth.view,
+                        depth_ops: Some(wgpu::Operations {
+                            load: wgpu::LoadOp::Clear(depth.config.clear_value),
+                            store: wgpu::StoreOp::Store,
+                        }),
+                        stencil_ops: None,
+                    }
+                }),
                 occlusion_query_set: None,
                 timestamp_writes: None,
             });
@@ -170,50 +411,441 @@ impl Display {
         }
 
         // Submit render pass
-        self.queue.submit(std::iter::once(encoder.finish()));
-        self.device.poll(wgpu::Maintain::Wait);
-        texture.present();
+        self.gpu.queue().submit(std::iter::once(encoder.finish()));
+        self.gpu.device().poll(wgpu::Maintain::Wait);
+
+        if let Some(texture) = surface_texture {
+            texture.present();
+        }
+
+        true
     }
+
+    /// The color to clear to.
+    ///
+    /// The clear alpha is only used if the surface is composited with alpha,
+    /// and the color is premultiplied by it for
+    /// [`wgpu::CompositeAlphaMode::PreMultiplied`].
+    fn clear_value(&self) -> wgpu::Color {
+        let (alpha, multiplier) = match self.config.alpha_mode {
+            wgpu::CompositeAlphaMode::PreMultiplied => (self.clear_alpha, self.clear_alpha),
+            wgpu::CompositeAlphaMode::PostMultiplied => (self.clear_alpha, 1.0),
+            _ => (1.0, 1.0),
+        };
+
+        wgpu::Color {
+            r: (self.clear_color.r() * multiplier) as f64,
+            g: (self.clear_color.g() * multiplier) as f64,
+            b: (self.clear_color.b() * multiplier) as f64,
+            a: alpha as f64,
+        }
+    }
+
+    /// Recreate the multisampled and depth textures for the size and the
+    /// sample count.
+    fn recreate_targets(&mut self) {
+        self.multisampled =
+            Self::create_multisampled_view(self.gpu.device(), &self.config, self.sample_count);
+        if let Some(depth) = &mut self.depth {
+            *depth = Depth::new(
+                self.gpu.device(),
+                &self.config,
+                depth.config,
+                self.sample_count,
+            );
+        }
+    }
+
+    /// The sample count if both the color and the depth formats support it,
+    /// `1` otherwise.
+    ///
+    /// The adapter specific format features are only used if the device is
+    /// created with them, otherwise only `1` and `4` are guaranteed.
+    fn supported_sample_count(
+        gpu: &Gpu,
+        config: &wgpu::SurfaceConfiguration,
+        depth: Option<&DepthConfig>,
+        sample_count: u32,
+    ) -> u32 {
+        let is_adapter_specific = gpu
+            .device()
+            .features()
+            .contains(wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES);
+        let is_supported = |format: wgpu::TextureFormat| match is_adapter_specific {
+            true => gpu
+                .adapter()
+                .get_texture_format_features(format)
+                .flags
+                .sample_count_supported(sample_count),
+            false => matches!(sample_count, 1 | 4),
+        };
+
+        match is_supported(config.format) && depth.is_none_or(|depth| is_supported(depth.format)) {
+            true => sample_count,
+            false => {
+                log::warn!("Sample count {sample_count} is not supported, falling back to 1");
+                1
+            }
+        }
+    }
+
+    /// Create the multisampled color texture resolved to the target, [`None`]
+    /// without multisampling.
+    fn create_multisampled_view(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        sample_count: u32,
+    ) -> Option<wgpu::TextureView> {
+        if sample_count <= 1 {
+            return None;
+        }
+
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Multisampled Texture"),
+            size: wgpu::Extent3d {
+                width: config.width,
+                height: config.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count,
+            dimension: wgpu::TextureDimension::D2,
+            format: config.format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        });
+
+        Some(texture.create_view(&wgpu::TextureViewDescriptor::default()))
+    }
+
+    fn create_offscreen_texture(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+    ) -> wgpu::Texture {
+        device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Offscreen Texture"),
+            size: wgpu::Extent3d {
+                width: config.width,
+                height: config.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: config.format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        })
+    }
+}
+
+/// Event emitted by the [`crate::systems::Scheduler`] when the [`Display`] is
+/// resized.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SurfaceResized {
+    pub width: u32,
+    pub height: u32,
+}
+
+/// The preferred configurations of the window surface of [`Display`], each
+/// falls back to the default if [`None`] or not supported by the surface.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct SurfacePreferences {
+    /// The texture format, the first sRGB one by default.
+    pub format: Option<wgpu::TextureFormat>,
+    /// The present mode, e.g. [`wgpu::PresentMode::AutoNoVsync`] to disable
+    /// vsync, the first supported one by default, or
+    /// [`wgpu::PresentMode::Fifo`] if the surface reports none.
+    pub present_mode: Option<wgpu::PresentMode>,
+    /// The alpha mode, e.g. [`wgpu::CompositeAlphaMode::PreMultiplied`] to
+    /// blend over what is behind the window with the clear alpha, the first
+    /// supported one by default, or [`wgpu::CompositeAlphaMode::Auto`] if the
+    /// surface reports none.
+    pub alpha_mode: Option<wgpu::CompositeAlphaMode>,
+}
+
+impl SurfacePreferences {
+    /// The preferred format if supported, otherwise the first sRGB one, or the
+    /// first one.
+    pub fn supported_format(
+        &self,
+        surface_caps: &wgpu::SurfaceCapabilities,
+    ) -> Option<wgpu::TextureFormat> {
+        match self.format {
+            Some(format) if surface_caps.formats.contains(&format) => return Some(format),
+            Some(format) => log::warn!("Surface format {format:?} is not supported"),
+            None => {}
+        }
+
+        surface_caps
+            .formats
+            .iter()
+            .find(|f| f.is_srgb())
+            .or(surface_caps.formats.first())
+            .copied()
+    }
+
+    /// The preferred present mode if supported, otherwise the first one.
+    ///
+    /// The automatic modes are always supported as they fall back by
+    /// themselves.
+    pub fn supported_present_mode(
+        &self,
+        surface_caps: &wgpu::SurfaceCapabilities,
+    ) -> wgpu::PresentMode {
+        match self.present_mode {
+            Some(
+                present_mode @ (wgpu::PresentMode::AutoVsync | wgpu::PresentMode::AutoNoVsync),
+            ) => return present_mode,
+            Some(present_mode) if surface_caps.present_modes.contains(&present_mode) => {
+                return present_mode
+            }
+            Some(present_mode) => log::warn!("Present mode {present_mode:?} is not supported"),
+            None => {}
+        }
+
+        surface_caps
+            .present_modes
+            .first()
+            .copied()
+            .unwrap_or(wgpu::PresentMode::Fifo)
+    }
+
+    /// The preferred alpha mode if supported, otherwise the first one.
+    pub fn supported_alpha_mode(
+        &self,
+        surface_caps: &wgpu::SurfaceCapabilities,
+    ) -> wgpu::CompositeAlphaMode {
+        match self.alpha_mode {
+            Some(alpha_mode @ wgpu::CompositeAlphaMode::Auto) => return alpha_mode,
+            Some(alpha_mode) if surface_caps.alpha_modes.contains(&alpha_mode) => {
+                return alpha_mode
+            }
+            Some(alpha_mode) => log::warn!("Alpha mode {alpha_mode:?} is not supported"),
+            None => {}
+        }
+
+        surface_caps
+            .alpha_modes
+            .first()
+            .copied()
+            .unwrap_or(wgpu::CompositeAlphaMode::Auto)
+    }
+}
+
+/// Configuration of the depth buffer of [`Display`].
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct DepthConfig {
+    /// The format of the depth texture.
+    pub format: wgpu::TextureFormat,
+    /// The comparison of the depth test.
+    pub compare: wgpu::CompareFunction,
+    /// Whether the depth of the fragments passing the test is written.
+    pub write_enabled: bool,
+    /// The value the depth is cleared to every frame.
+    pub clear_value: f32,
+}
+
+impl DepthConfig {
+    /// The depth stencil state of the render pipelines, without stencil.
+    pub fn depth_stencil_state(&self) -> wgpu::DepthStencilState {
+        wgpu::DepthStencilState {
+            format: self.format,
+            depth_write_enabled: self.write_enabled,
+            depth_compare: self.compare,
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState::default(),
+        }
+    }
+}
+
+impl Default for DepthConfig {
+    fn default() -> Self {
+        Self {
+            format: wgpu::TextureFormat::Depth32Float,
+            compare: wgpu::CompareFunction::Less,
+            write_enabled: true,
+            clear_value: 1.0,
+        }
+    }
+}
+
+/// The depth buffer of [`Display`], recreated when it is resized or its
+/// sample count changes.
+struct Depth {
+    config: DepthConfig,
+    view: wgpu::TextureView,
+}
+
+impl Depth {
+    fn new(
+        device: &wgpu::Device,
+        surface_config: &wgpu::SurfaceConfiguration,
+        config: DepthConfig,
+        sample_count: u32,
+    ) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Depth Texture"),
+            size: wgpu::Extent3d {
+                width: surface_config.width,
+                height: surface_config.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count,
+            dimension: wgpu::TextureDimension::D2,
+            format: config.format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        Self { config, view }
+    }
+}
+
+/// The target of [`Display`].
+enum DisplayTarget {
+    Surface {
+        // Shared with the GPU context recovery, which needs it to find a
+        // compatible adapter
+        surface: Arc<wgpu::Surface<'static>>,
+
+        // This is needed because surface points to the window
+        window: Arc<Window>,
+    },
+    Texture(wgpu::Texture),
 }
 
 /// Builder of [`Display`].
-pub struct DisplayBuilder<T> {
-    window: T,
+pub struct DisplayBuilder<T, U> {
+    gpu: T,
+    target: U,
     clear_color: RgbColor,
+    clear_alpha: f32,
+    surface_preferences: SurfacePreferences,
+    depth: Option<DepthConfig>,
+    sample_count: u32,
 }
 
 pub mod builder {
     use super::*;
 
-    pub struct NoWindow;
-    pub struct WithWindow(pub Arc<Window>);
+    pub struct NoGpu;
+    pub struct WithGpu(pub Arc<Gpu>);
+
+    pub struct NoTarget;
+    pub struct WithTarget(pub RenderTarget);
 }
 
-impl DisplayBuilder<builder::NoWindow> {
+impl DisplayBuilder<builder::NoGpu, builder::NoTarget> {
     pub fn new() -> Self {
         Self {
-            window: builder::NoWindow,
+            gpu: builder::NoGpu,
+            target: builder::NoTarget,
             clear_color: RgbColor::BLACK,
+            clear_alpha: 1.0,
+            surface_preferences: SurfacePreferences::default(),
+            depth: Some(DepthConfig::default()),
+            sample_count: 1,
         }
     }
 }
 
-impl<T> DisplayBuilder<T> {
-    pub fn with_window(self, window: Arc<Window>) -> DisplayBuilder<builder::WithWindow> {
+impl<T, U> DisplayBuilder<T, U> {
+    pub fn with_gpu(self, gpu: Arc<Gpu>) -> DisplayBuilder<builder::WithGpu, U> {
+        DisplayBuilder {
+            gpu: builder::WithGpu(gpu),
+            target: self.target,
+            clear_color: self.clear_color,
+            clear_alpha: self.clear_alpha,
+            surface_preferences: self.surface_preferences,
+            depth: self.depth,
+            sample_count: self.sample_count,
+        }
+    }
+
+    pub fn with_target(self, target: RenderTarget) -> DisplayBuilder<T, builder::WithTarget> {
         DisplayBuilder {
-            window: builder::WithWindow(window),
+            gpu: self.gpu,
+            target: builder::WithTarget(target),
             clear_color: self.clear_color,
+            clear_alpha: self.clear_alpha,
+            surface_preferences: self.surface_preferences,
+            depth: self.depth,
+            sample_count: self.sample_count,
         }
     }
 
+    pub fn with_window(self, window: Arc<Window>) -> DisplayBuilder<T, builder::WithTarget> {
+        self.with_target(RenderTarget::Window(window))
+    }
+
     pub fn with_clear_color(mut self, clear_color: RgbColor) -> Self {
         self.clear_color = clear_color;
         self
     }
+
+    /// Set the alpha of the clear color, it is `1.0` by default.
+    ///
+    /// It is only used if the window surface is composited with alpha, see
+    /// [`SurfacePreferences::alpha_mode`].
+    pub fn with_clear_alpha(mut self, clear_alpha: f32) -> Self {
+        self.clear_alpha = clear_alpha.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Set the preferred configurations of the window surface, ignored for
+    /// headless targets.
+    pub fn with_surface_preferences(mut self, surface_preferences: SurfacePreferences) -> Self {
+        self.surface_preferences = surface_preferences;
+        self
+    }
+
+    /// Set the preferred texture format of the window surface.
+    pub fn with_surface_format(mut self, format: wgpu::TextureFormat) -> Self {
+        self.surface_preferences.format = Some(format);
+        self
+    }
+
+    /// Set the preferred present mode of the window surface.
+    pub fn with_present_mode(mut self, present_mode: wgpu::PresentMode) -> Self {
+        self.surface_preferences.present_mode = Some(present_mode);
+        self
+    }
+
+    /// Set the preferred alpha mode of the window surface.
+    pub fn with_alpha_mode(mut self, alpha_mode: wgpu::CompositeAlphaMode) -> Self {
+        self.surface_preferences.alpha_mode = Some(alpha_mode);
+        self
+    }
+
+    /// Set the depth buffer, or disable it with [`None`].
+    ///
+    /// There is a default [`DepthConfig`] otherwise.
+    pub fn with_depth(mut self, depth: Option<DepthConfig>) -> Self {
+        self.depth = depth;
+        self
+    }
+
+    /// Set the number of samples per pixel for multisample anti-aliasing, it
+    /// is `1` by default.
+    pub fn with_sample_count(mut self, sample_count: u32) -> Self {
+        self.sample_count = sample_count;
+        self
+    }
 }
 
-impl DisplayBuilder<builder::WithWindow> {
-    pub async fn build(self) -> Display {
-        Display::new(self.window.0, self.clear_color).await
+impl DisplayBuilder<builder::WithGpu, builder::WithTarget> {
+    pub fn build(self) -> Result<Display, Error> {
+        Display::new(
+            self.gpu.0,
+            self.target.0,
+            self.clear_color,
+            self.clear_alpha,
+            self.surface_preferences,
+            self.depth,
+            self.sample_count,
+        )
     }
 }
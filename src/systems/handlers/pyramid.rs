@@ -1,9 +1,17 @@
-use std::sync::mpsc;
+use std::sync::Arc;
 
 use glam::*;
+use serde::{Deserialize, Serialize};
 use wgpu::util::DeviceExt;
 
-use crate::systems::{EngineOutSignal, PyramidTransformUpdateSignal, RgbColor, Transform};
+use crate::{
+    engine::{signal::OutSignalSender, Gpu},
+    systems::{
+        handlers::{CameraBinding, DeltaTime, Display, TimeScale},
+        Context, Error, Handler, InitContext, PyramidTransformUpdateSignal, RgbColor, Signal,
+        Transform,
+    },
+};
 
 /// Handler for the spinning pyramid.
 pub struct Pyramid {
@@ -14,21 +22,30 @@ pub struct Pyramid {
     model_buffer: wgpu::Buffer,
     index_buffer: wgpu::Buffer,
     render_pipeline: wgpu::RenderPipeline,
+    render_target: PyramidRenderTarget,
 
+    transform_bind_group_layout: wgpu::BindGroupLayout,
     transform_bind_group: wgpu::BindGroup,
 
     is_transform_dirty: bool,
     is_model_dirty: bool,
+
+    // Interpolated from when there is a fixed timestep
+    previous_transform: Option<Transform>,
+    alpha: Option<f32>,
 }
 
 impl Pyramid {
     pub fn new(
         device: &wgpu::Device,
-        surface_config: &wgpu::SurfaceConfiguration,
+        pipeline_cache: Option<&wgpu::PipelineCache>,
+        render_target: PyramidRenderTarget,
         camera_bind_group_layout: &wgpu::BindGroupLayout,
         transform: PyramidTransform,
         model: PyramidModel,
-    ) -> Self {
+    ) -> Result<Self, Error> {
+        model.validate()?;
+
         let indices = model.indices().collect::<Vec<_>>();
 
         log::debug!("Creating pyramid transform buffer");
@@ -78,61 +95,17 @@ impl Pyramid {
             usage: wgpu::BufferUsages::INDEX,
         });
 
-        log::debug!("Creating pyramid shader");
-        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: Some("Pyramid Shader"),
-            source: wgpu::ShaderSource::Wgsl(include_str!("../shaders/pyramid.wgsl").into()),
-        });
-
-        log::debug!("Creating pyramid pipeline layout");
-        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-            label: Some("Pyramid Pipeline Layout"),
-            bind_group_layouts: &[camera_bind_group_layout, &transform_bind_group_layout],
-            push_constant_ranges: &[],
-        });
-
-        log::debug!("Creating pyramid render pipeline");
-        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("Pyramid Render Pipeline"),
-            layout: Some(&pipeline_layout),
-            vertex: wgpu::VertexState {
-                module: &shader,
-                entry_point: Some("vertex_main"),
-                buffers: &[PyramidVertex::BUFFER_LAYOUT],
-                compilation_options: wgpu::PipelineCompilationOptions::default(),
-            },
-            fragment: Some(wgpu::FragmentState {
-                module: &shader,
-                entry_point: Some("fragment_main"),
-                targets: &[Some(wgpu::ColorTargetState {
-                    format: surface_config.format,
-                    blend: Some(wgpu::BlendState::REPLACE),
-                    write_mask: wgpu::ColorWrites::ALL,
-                })],
-                compilation_options: wgpu::PipelineCompilationOptions::default(),
-            }),
-            primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleList,
-                strip_index_format: None,
-                front_face: wgpu::FrontFace::Ccw,
-                cull_mode: Some(wgpu::Face::Back),
-                polygon_mode: wgpu::PolygonMode::Fill,
-                unclipped_depth: false,
-                conservative: false,
-            },
-            depth_stencil: None,
-            multisample: wgpu::MultisampleState {
-                count: 1,
-                mask: !0,
-                alpha_to_coverage_enabled: false,
-            },
-            multiview: None,
-            cache: None,
-        });
+        let render_pipeline = Self::create_render_pipeline(
+            device,
+            pipeline_cache,
+            camera_bind_group_layout,
+            &transform_bind_group_layout,
+            &render_target,
+        );
 
         log::info!("Pyramid handler initialized");
 
-        Self {
+        Ok(Self {
             transform,
             model,
 
@@ -140,12 +113,17 @@ impl Pyramid {
             model_buffer,
             index_buffer,
             render_pipeline,
+            render_target,
 
+            transform_bind_group_layout,
             transform_bind_group,
 
             is_transform_dirty: false,
             is_model_dirty: false,
-        }
+
+            previous_transform: None,
+            alpha: None,
+        })
     }
 
     /// Returns the transform of the pyramid.
@@ -171,6 +149,7 @@ impl Pyramid {
     /// Sets the transform of the pyramid.
     pub fn set_transform(&mut self, transform: PyramidTransform) {
         self.transform = transform;
+        self.previous_transform = None;
         self.is_transform_dirty = true;
     }
 
@@ -202,12 +181,29 @@ impl Pyramid {
 
     pub fn update(&mut self, dt: f32) {
         let rotation = self.transform().auto_rotation_speed * dt;
+        if rotation == 0.0 {
+            return;
+        }
+
         self.transform_mut()
             .transform
             .rotate(Quat::from_axis_angle(Vec3::Y, rotation));
     }
 
-    pub fn signal(&self, tx: &mpsc::Sender<EngineOutSignal>) {
+    /// Update by a tick of the fixed timestep, keeping the previous transform to
+    /// interpolate from.
+    pub fn fixed_update(&mut self, dt: f32) {
+        self.previous_transform = Some(self.transform.transform.clone());
+        self.update(dt);
+    }
+
+    /// Sets the alpha to render the transform interpolated from the previous
+    /// tick, see [`crate::engine::FixedStep::alpha`].
+    pub fn set_interpolation(&mut self, alpha: Option<f32>) {
+        self.alpha = alpha;
+    }
+
+    pub fn signal(&self, tx: &OutSignalSender<Signal>) {
         if self.is_transform_dirty {
             tx.send(PyramidTransformUpdateSignal::out_signal(
                 self.transform.clone(),
@@ -222,12 +218,12 @@ impl Pyramid {
         render_pass: &mut wgpu::RenderPass,
         camera_bind_group: &wgpu::BindGroup,
     ) {
-        // Update buffers if dirty
-        if self.is_transform_dirty {
+        // Update buffers if dirty or interpolated
+        if self.is_transform_dirty || self.alpha.is_some() {
             queue.write_buffer(
                 &self.transform_buffer,
                 0,
-                self.transform.buffer().as_bytes(),
+                self.render_transform().as_bytes(),
             );
             self.is_transform_dirty = false;
         }
@@ -253,9 +249,263 @@ impl Pyramid {
         );
         render_pass.draw_indexed(0..self.model.side_count as u32 * 3, 0, 0..1);
     }
+
+    /// Recreate the GPU resources on another device, e.g. after the device is
+    /// lost, keeping the transform and the model.
+    pub fn recreate(
+        &mut self,
+        device: &wgpu::Device,
+        pipeline_cache: Option<&wgpu::PipelineCache>,
+        render_target: PyramidRenderTarget,
+        camera_bind_group_layout: &wgpu::BindGroupLayout,
+    ) -> Result<(), Error> {
+        let pyramid = Self::new(
+            device,
+            pipeline_cache,
+            render_target,
+            camera_bind_group_layout,
+            self.transform.clone(),
+            self.model.clone(),
+        )?;
+
+        self.transform_buffer = pyramid.transform_buffer;
+        self.model_buffer = pyramid.model_buffer;
+        self.index_buffer = pyramid.index_buffer;
+        self.render_pipeline = pyramid.render_pipeline;
+        self.render_target = pyramid.render_target;
+        self.transform_bind_group_layout = pyramid.transform_bind_group_layout;
+        self.transform_bind_group = pyramid.transform_bind_group;
+
+        Ok(())
+    }
+
+    /// The render target the render pipeline is built for.
+    pub fn render_target(&self) -> &PyramidRenderTarget {
+        &self.render_target
+    }
+
+    /// Rebuild the render pipeline for another render target.
+    pub fn set_render_target(
+        &mut self,
+        device: &wgpu::Device,
+        pipeline_cache: Option<&wgpu::PipelineCache>,
+        camera_bind_group_layout: &wgpu::BindGroupLayout,
+        render_target: PyramidRenderTarget,
+    ) {
+        self.render_pipeline = Self::create_render_pipeline(
+            device,
+            pipeline_cache,
+            camera_bind_group_layout,
+            &self.transform_bind_group_layout,
+            &render_target,
+        );
+        self.render_target = render_target;
+    }
+
+    fn create_render_pipeline(
+        device: &wgpu::Device,
+        pipeline_cache: Option<&wgpu::PipelineCache>,
+        camera_bind_group_layout: &wgpu::BindGroupLayout,
+        transform_bind_group_layout: &wgpu::BindGroupLayout,
+        render_target: &PyramidRenderTarget,
+    ) -> wgpu::RenderPipeline {
+        log::debug!("Creating pyramid shader");
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Pyramid Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("../shaders/pyramid.wgsl").into()),
+        });
+
+        log::debug!("Creating pyramid pipeline layout");
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Pyramid Pipeline Layout"),
+            bind_group_layouts: &[camera_bind_group_layout, transform_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        log::debug!("Creating pyramid render pipeline");
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Pyramid Render Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vertex_main"),
+                buffers: &[PyramidVertex::BUFFER_LAYOUT],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fragment_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: render_target.format,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: Some(wgpu::Face::Back),
+                polygon_mode: wgpu::PolygonMode::Fill,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            depth_stencil: render_target.depth_stencil.clone(),
+            multisample: render_target.multisample,
+            multiview: None,
+            cache: pipeline_cache,
+        })
+    }
+
+    /// The transform buffer, interpolated if there is an alpha.
+    fn render_transform(&self) -> PyramidTransformBuffer {
+        match (&self.previous_transform, self.alpha) {
+            (Some(previous), Some(alpha)) => PyramidTransformBuffer {
+                transform: previous.lerp(&self.transform.transform, alpha).matrix(),
+            },
+            _ => self.transform.buffer(),
+        }
+    }
 }
 
-#[derive(Debug, Clone)]
+impl Handler for Pyramid {
+    fn init(ctx: &mut InitContext) -> Result<Self, Error> {
+        PyramidBuilder::new()
+            .with_gpu(ctx.resources.require::<Arc<Gpu>>()?)
+            .with_surface_config(ctx.display.config())
+            .with_depth_stencil(ctx.display.depth_stencil_state())
+            .with_multisample(ctx.display.multisample_state())
+            .with_camera_bind_group_layout(
+                &ctx.resources.require::<CameraBinding>()?.bind_group_layout,
+            )
+            .with_pyramid_transform(ctx.args.pyramid_transform.clone())
+            .with_model(ctx.args.pyramid_model.clone())
+            .build()
+    }
+
+    /// Recreate the GPU resources with the new [`CameraBinding`].
+    fn device_recovered(&mut self, ctx: &mut Context) {
+        match (
+            ctx.resources.get::<Arc<Gpu>>(),
+            ctx.resources.get::<CameraBinding>(),
+        ) {
+            (Some(gpu), Some(camera)) => {
+                if let Err(e) = self.recreate(
+                    gpu.device(),
+                    gpu.pipeline_cache(),
+                    PyramidRenderTarget::new(ctx.display),
+                    &camera.bind_group_layout,
+                ) {
+                    log::error!("Pyramid failed to recover: {e}");
+                }
+            }
+            _ => log::warn!("Pyramid not recovered, GPU or camera missing"),
+        }
+
+        ctx.request_redraw();
+    }
+
+    fn signal(&mut self, _: &mut Context, signal: &Signal) {
+        match signal {
+            Signal::PyramidTransformUpdate(update) => {
+                log::debug!("Pyramid transform incoming signal");
+                self.set_transform(update.transform.clone());
+            }
+            Signal::PyramidModelUpdate(update) => {
+                log::debug!("Pyramid model incoming signal");
+                match update.model.validate() {
+                    Ok(()) => self.set_model(update.model.clone()),
+                    Err(e) => log::error!("Pyramid model incoming signal ignored: {e}"),
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Rotate the pyramid by the tick scaled by [`TimeScale`].
+    fn fixed_update(&mut self, ctx: &mut Context) {
+        if let Some(step) = ctx.items.fixed_step {
+            let TimeScale(scale) = ctx.resources.get().copied().unwrap_or(TimeScale(1.0));
+            self.fixed_update(step.tick.as_secs_f32() * scale);
+        }
+    }
+
+    /// Rotate the pyramid if there is no fixed timestep, then send its
+    /// transform if it changed.
+    ///
+    /// A redraw is requested while it changes or rotates, and the render
+    /// pipeline is rebuilt if the [`Display`] no longer matches it.
+    fn update(&mut self, ctx: &mut Context) {
+        let render_target = PyramidRenderTarget::new(ctx.display);
+        if render_target != self.render_target {
+            match (
+                ctx.resources.get::<Arc<Gpu>>(),
+                ctx.resources.get::<CameraBinding>(),
+            ) {
+                (Some(gpu), Some(camera)) => {
+                    log::debug!("Rebuilding pyramid render pipeline");
+                    self.set_render_target(
+                        gpu.device(),
+                        gpu.pipeline_cache(),
+                        &camera.bind_group_layout,
+                        render_target,
+                    );
+                }
+                _ => log::warn!("Pyramid render pipeline not rebuilt, GPU or camera missing"),
+            }
+        }
+
+        match ctx.items.fixed_step {
+            Some(step) => self.set_interpolation(Some(step.alpha)),
+            None => {
+                self.set_interpolation(None);
+                if let Some(DeltaTime(delta)) = ctx.resources.get() {
+                    self.update(*delta);
+                }
+            }
+        }
+
+        let TimeScale(scale) = ctx.resources.get().copied().unwrap_or(TimeScale(1.0));
+        let is_rotating = self.transform().auto_rotation_speed != 0.0 && scale > 0.0;
+        if self.is_transform_dirty || self.is_model_dirty || is_rotating {
+            ctx.request_redraw();
+        }
+
+        if let Some(tx) = ctx.items.tx.as_ref() {
+            Pyramid::signal(self, tx);
+        }
+    }
+
+    fn render(&mut self, ctx: &mut Context, pass: &mut wgpu::RenderPass) {
+        let Some(camera) = ctx.resources.get::<CameraBinding>() else {
+            return;
+        };
+
+        self.render(ctx.display.queue(), pass, &camera.bind_group);
+    }
+}
+
+/// The render target state the render pipeline of [`Pyramid`] must match.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PyramidRenderTarget {
+    pub format: wgpu::TextureFormat,
+    pub depth_stencil: Option<wgpu::DepthStencilState>,
+    pub multisample: wgpu::MultisampleState,
+}
+
+impl PyramidRenderTarget {
+    /// The render target state of the [`Display`].
+    pub fn new(display: &Display) -> Self {
+        Self {
+            format: display.config().format,
+            depth_stencil: display.depth_stencil_state(),
+            multisample: display.multisample_state(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PyramidTransform {
     pub transform: Transform,
     pub auto_rotation_speed: f32,
@@ -290,7 +540,7 @@ impl PyramidTransformBuffer {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PyramidModel {
     pub height: f32,
     pub base_radius: f32,
@@ -308,6 +558,14 @@ impl PyramidModel {
         })
     }
 
+    /// Check that the side count fits in the model buffer.
+    pub fn validate(&self) -> Result<(), Error> {
+        match (3..=PyramidModelBuffer::MAX_SIDES).contains(&self.side_count) {
+            true => Ok(()),
+            false => Err(Error::PyramidSideCount(self.side_count)),
+        }
+    }
+
     fn buffer(&self) -> PyramidModelBuffer {
         PyramidModelBuffer::new(self.height, self.base_radius, self.side_count)
     }
@@ -393,13 +651,15 @@ pub struct PyramidBuilder<T, U, V> {
     device: T,
     surface_config: U,
     camera_bind_group_layout: V,
+    depth_stencil: Option<wgpu::DepthStencilState>,
+    multisample: wgpu::MultisampleState,
     transform: PyramidTransform,
     model: PyramidModel,
 }
 
 pub mod builder {
     pub struct NoDevice;
-    pub struct WithDevice<'a>(pub &'a wgpu::Device);
+    pub struct WithDevice<'a>(pub &'a wgpu::Device, pub Option<&'a wgpu::PipelineCache>);
 
     pub struct NoSurfaceConfig;
     pub struct WithSurfaceConfig<'a>(pub &'a wgpu::SurfaceConfiguration);
@@ -414,6 +674,8 @@ impl PyramidBuilder<builder::NoDevice, builder::NoSurfaceConfig, builder::NoCame
             device: builder::NoDevice,
             surface_config: builder::NoSurfaceConfig,
             camera_bind_group_layout: builder::NoCameraBindGroupLayout,
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
             transform: PyramidTransform::default(),
             model: PyramidModel::default(),
         }
@@ -423,9 +685,24 @@ impl PyramidBuilder<builder::NoDevice, builder::NoSurfaceConfig, builder::NoCame
 impl<T, U, V> PyramidBuilder<T, U, V> {
     pub fn with_device(self, device: &wgpu::Device) -> PyramidBuilder<builder::WithDevice, U, V> {
         PyramidBuilder {
-            device: builder::WithDevice(device),
+            device: builder::WithDevice(device, None),
             surface_config: self.surface_config,
             camera_bind_group_layout: self.camera_bind_group_layout,
+            depth_stencil: self.depth_stencil,
+            multisample: self.multisample,
+            transform: self.transform,
+            model: self.model,
+        }
+    }
+
+    /// Set the device and the pipeline cache of the [`Gpu`].
+    pub fn with_gpu(self, gpu: &Gpu) -> PyramidBuilder<builder::WithDevice<'_>, U, V> {
+        PyramidBuilder {
+            device: builder::WithDevice(gpu.device(), gpu.pipeline_cache()),
+            surface_config: self.surface_config,
+            camera_bind_group_layout: self.camera_bind_group_layout,
+            depth_stencil: self.depth_stencil,
+            multisample: self.multisample,
             transform: self.transform,
             model: self.model,
         }
@@ -439,6 +716,8 @@ impl<T, U, V> PyramidBuilder<T, U, V> {
             device: self.device,
             surface_config: builder::WithSurfaceConfig(surface_config),
             camera_bind_group_layout: self.camera_bind_group_layout,
+            depth_stencil: self.depth_stencil,
+            multisample: self.multisample,
             transform: self.transform,
             model: self.model,
         }
@@ -452,11 +731,29 @@ impl<T, U, V> PyramidBuilder<T, U, V> {
             device: self.device,
             surface_config: self.surface_config,
             camera_bind_group_layout: builder::WithCameraBindGroupLayout(camera_bind_group_layout),
+            depth_stencil: self.depth_stencil,
+            multisample: self.multisample,
             transform: self.transform,
             model: self.model,
         }
     }
 
+    /// Set the depth stencil state, which must match the depth buffer of the
+    /// [`crate::systems::handlers::Display`], see
+    /// [`crate::systems::handlers::Display::depth_stencil_state`].
+    pub fn with_depth_stencil(mut self, depth_stencil: Option<wgpu::DepthStencilState>) -> Self {
+        self.depth_stencil = depth_stencil;
+        self
+    }
+
+    /// Set the multisample state, which must match the sample count of the
+    /// [`crate::systems::handlers::Display`], see
+    /// [`crate::systems::handlers::Display::multisample_state`].
+    pub fn with_multisample(mut self, multisample: wgpu::MultisampleState) -> Self {
+        self.multisample = multisample;
+        self
+    }
+
     pub fn with_pyramid_transform(mut self, transform: PyramidTransform) -> Self {
         self.transform = transform;
         self
@@ -515,10 +812,15 @@ impl<'a>
         builder::WithCameraBindGroupLayout<'a>,
     >
 {
-    pub fn build(self) -> Pyramid {
+    pub fn build(self) -> Result<Pyramid, Error> {
         Pyramid::new(
             self.device.0,
-            self.surface_config.0,
+            self.device.1,
+            PyramidRenderTarget {
+                format: self.surface_config.0.format,
+                depth_stencil: self.depth_stencil,
+                multisample: self.multisample,
+            },
             self.camera_bind_group_layout.0,
             self.transform,
             self.model,
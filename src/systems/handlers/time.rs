@@ -1,25 +1,43 @@
-use std::sync::Arc;
+use std::time::Duration;
 
-use chrono::prelude::*;
-use winit::window::Window;
+use winit::event::{DeviceEvent, WindowEvent};
 
-use crate::{engine::utils, systems::FpsLimit};
+use crate::{
+    engine::{Clock, Items, MonotonicClock, NextFrame, RenderTarget},
+    systems::{Context, Error, FpsLimit, Handler, InitContext, RedrawPolicy, Signal},
+};
 
 /// Handler for time-related operations.
 pub struct Time {
+    clock: Box<dyn Clock>,
     fps_limit: FpsLimit,
+    redraw_policy: RedrawPolicy,
     delta: f32,
-    frame_timer: DateTime<Utc>,
-    start_timer: DateTime<Utc>,
+    frame_timer: Duration,
+    start_timer: Duration,
+    paused_timer: Option<Duration>,
+    time_scale: f32,
+    is_simulation_paused: bool,
+    pending_steps: u32,
+    simulation_scale: f32,
 }
 
 impl Time {
-    pub fn new(fps_limit: FpsLimit) -> Self {
+    pub fn new(fps_limit: FpsLimit, clock: Box<dyn Clock>) -> Self {
+        let now = clock.now();
+
         Self {
+            clock,
             fps_limit,
+            redraw_policy: RedrawPolicy::default(),
             delta: 0.0,
-            frame_timer: Utc::now(),
-            start_timer: Utc::now(),
+            frame_timer: now,
+            start_timer: now,
+            paused_timer: None,
+            time_scale: 1.0,
+            is_simulation_paused: false,
+            pending_steps: 0,
+            simulation_scale: 1.0,
         }
     }
 
@@ -28,62 +46,266 @@ impl Time {
         self.delta = self.time_since_last_frame();
 
         // Update frame timer
-        self.frame_timer = Utc::now();
+        self.frame_timer = self.clock.now();
+    }
+
+    /// Override the delta of the current frame.
+    ///
+    /// This is for [`crate::engine::Items::frame_delta`], when the engine records
+    /// or replays a session.
+    pub fn set_delta(&mut self, delta: f32) {
+        self.delta = delta;
+    }
+
+    pub fn set_redraw_policy(&mut self, redraw_policy: RedrawPolicy) {
+        self.redraw_policy = redraw_policy;
+    }
+
+    pub fn redraw_policy(&self) -> RedrawPolicy {
+        self.redraw_policy
+    }
+
+    /// Request the next frame following the [`RedrawPolicy`].
+    ///
+    /// The engine waits for the timeout of the [`FpsLimit`] without blocking
+    /// the event loop. With [`RedrawPolicy::OnDemand`], no frame is requested
+    /// unless `is_redraw_requested`.
+    pub fn end_frame<T>(&self, items: &mut Items<T>, is_redraw_requested: bool) {
+        let next_frame = match self.redraw_policy {
+            RedrawPolicy::Continuous if !items.target.is_headless() => NextFrame::Now,
+            RedrawPolicy::OnDemand if !is_redraw_requested => NextFrame::Idle,
+            _ => self.next_frame(&items.target),
+        };
+        items.request_frame(next_frame);
+    }
+
+    /// The next frame of the target to keep to the [`FpsLimit`].
+    pub fn next_frame(&self, target: &RenderTarget) -> NextFrame {
+        // Headless targets are driven frame by frame
+        if target.is_headless() {
+            return NextFrame::Idle;
+        }
+
+        match self.frame_timeout() {
+            Some(timeout) => NextFrame::After(Duration::from_secs_f32(timeout)),
+            None => NextFrame::Now,
+        }
     }
 
-    pub fn end_frame(&mut self, window: Arc<Window>) {
+    /// The time to wait before the next frame to keep to the [`FpsLimit`], if
+    /// any.
+    pub fn frame_timeout(&self) -> Option<f32> {
         let since_last = self.time_since_last_frame();
 
-        // Limit the frame rate
-        match self.fps_limit.as_secs_f32() {
-            Some(secs) if since_last < secs => Self::set_timeout_redraw(window, secs - since_last),
-            _ => window.request_redraw(),
+        self.fps_limit
+            .as_secs_f32()
+            .filter(|secs| since_last < *secs)
+            .map(|secs| secs - since_last)
+    }
+
+    /// Pause the timers.
+    pub fn pause(&mut self) {
+        if self.paused_timer.is_none() {
+            self.paused_timer = Some(self.clock.now());
+        }
+    }
+
+    /// Resume the timers.
+    ///
+    /// The paused duration is excluded from [`Time::delta`] and [`Time::elapsed`].
+    pub fn resume(&mut self) {
+        if let Some(paused_timer) = self.paused_timer.take() {
+            let paused = self.clock.now().saturating_sub(paused_timer);
+            self.frame_timer += paused;
+            self.start_timer += paused;
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused_timer.is_some()
+    }
+
+    /// Set the factor of the simulation time, it is clamped to be non-negative.
+    pub fn set_time_scale(&mut self, time_scale: f32) {
+        self.time_scale = time_scale.max(0.0);
+    }
+
+    pub fn time_scale(&self) -> f32 {
+        self.time_scale
+    }
+
+    /// Pause the simulation time, frames are still rendered.
+    pub fn pause_simulation(&mut self) {
+        self.is_simulation_paused = true;
+    }
+
+    /// Resume the simulation time, dropping the steps not taken yet.
+    pub fn resume_simulation(&mut self) {
+        self.is_simulation_paused = false;
+        self.pending_steps = 0;
+    }
+
+    pub fn is_simulation_paused(&self) -> bool {
+        self.is_simulation_paused
+    }
+
+    /// Step the simulation by one frame while it is paused, or by one tick if
+    /// there is a fixed timestep.
+    pub fn step(&mut self) {
+        if self.is_simulation_paused {
+            self.pending_steps += 1;
         }
     }
 
+    /// The scale of the next frame or tick of the simulation, taking a pending
+    /// step if the simulation is paused.
+    pub fn take_simulation_scale(&mut self) -> f32 {
+        self.simulation_scale = match (self.is_simulation_paused, self.pending_steps) {
+            (false, _) => self.time_scale,
+            (true, 0) => 0.0,
+            (true, _) => {
+                self.pending_steps -= 1;
+                self.time_scale
+            }
+        };
+
+        self.simulation_scale
+    }
+
+    /// The real delta of the frame, see [`Time::simulation_delta`] for the
+    /// scaled one.
     pub fn delta(&self) -> f32 {
         self.delta
     }
 
+    /// The delta of the frame scaled by the time scale, zero while the
+    /// simulation is paused.
+    pub fn simulation_delta(&self) -> f32 {
+        self.delta * self.simulation_scale
+    }
+
     pub fn elapsed(&self) -> f32 {
-        Utc::now()
-            .signed_duration_since(self.start_timer)
-            .num_nanoseconds()
-            .expect("nanoseconds since start") as f32
-            * 1e-9
+        self.clock
+            .now()
+            .saturating_sub(self.start_timer)
+            .as_secs_f32()
     }
 
     pub fn time_since_last_frame(&self) -> f32 {
-        Utc::now()
-            .signed_duration_since(self.frame_timer)
-            .num_nanoseconds()
-            .expect("nanoseconds since last frame") as f32
-            * 1e-9
-    }
-
-    fn set_timeout_redraw(window: Arc<Window>, duration: f32) {
-        cfg_if::cfg_if! {
-            if #[cfg(target_arch = "wasm32")] {
-                utils::set_timeout(move || {
-                    window.request_redraw();
-                }, (duration * 1000.0) as i32)
-            } else {
-                std::thread::sleep(std::time::Duration::from_secs_f32(duration));
-                window.request_redraw();
+        self.clock
+            .now()
+            .saturating_sub(self.frame_timer)
+            .as_secs_f32()
+    }
+}
+
+impl Handler for Time {
+    fn init(ctx: &mut InitContext) -> Result<Self, Error> {
+        Ok(TimeBuilder::new()
+            .with_fps_limit(ctx.args.fps_limit)
+            .with_redraw_policy(ctx.args.redraw_policy)
+            .build())
+    }
+
+    /// Request a frame on input with [`RedrawPolicy::OnDemand`].
+    fn window_event(&mut self, ctx: &mut Context, event: &WindowEvent) {
+        if self.redraw_policy.is_on_demand() && !matches!(event, WindowEvent::RedrawRequested) {
+            ctx.items.request_frame(self.next_frame(&ctx.items.target));
+        }
+    }
+
+    /// Request a frame on input with [`RedrawPolicy::OnDemand`].
+    fn device_event(&mut self, ctx: &mut Context, _: &DeviceEvent) {
+        if self.redraw_policy.is_on_demand() {
+            ctx.items.request_frame(self.next_frame(&ctx.items.target));
+        }
+    }
+
+    fn pause(&mut self, _: &mut Context) {
+        self.pause();
+    }
+
+    fn resume(&mut self, _: &mut Context) {
+        self.resume();
+    }
+
+    fn signal(&mut self, ctx: &mut Context, signal: &Signal) {
+        ctx.request_redraw();
+
+        match signal {
+            Signal::TimeScale(update) => {
+                log::debug!("Time scale incoming signal: {}", update.scale);
+                self.set_time_scale(update.scale);
             }
+            Signal::SimulationPause(update) => {
+                log::debug!("Simulation pause incoming signal: {}", update.paused);
+                match update.paused {
+                    true => self.pause_simulation(),
+                    false => self.resume_simulation(),
+                }
+            }
+            Signal::SimulationStep(..) => {
+                log::debug!("Simulation step incoming signal");
+                self.step();
+            }
+            _ => {}
         }
     }
+
+    /// Publish the scale of the tick as [`TimeScale`].
+    fn fixed_update(&mut self, ctx: &mut Context) {
+        let scale = self.take_simulation_scale();
+        ctx.resources.insert(TimeScale(scale));
+    }
+
+    /// Update the delta and publish the scaled one as [`DeltaTime`].
+    ///
+    /// With a fixed timestep, steps are taken by the ticks instead.
+    fn pre_update(&mut self, ctx: &mut Context) {
+        self.update();
+        if let Some(delta) = ctx.items.frame_delta {
+            self.set_delta(delta.as_secs_f32());
+        }
+
+        let scale = match ctx.items.fixed_step {
+            Some(..) if self.is_simulation_paused() => 0.0,
+            Some(..) => self.time_scale(),
+            None => self.take_simulation_scale(),
+        };
+        self.simulation_scale = scale;
+
+        ctx.resources.insert(TimeScale(scale));
+        ctx.resources.insert(DeltaTime(self.simulation_delta()));
+    }
+
+    fn post_render(&mut self, ctx: &mut Context) {
+        self.end_frame(ctx.items, ctx.is_redraw_requested());
+    }
 }
 
+/// Delta time of the frame in seconds, scaled by the time scale, published as
+/// a resource by [`Time`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DeltaTime(pub f32);
+
+/// Scale of the simulation time of the frame or tick, zero while it is paused,
+/// published as a resource by [`Time`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TimeScale(pub f32);
+
 /// Builder of [`Time`].
 pub struct TimeBuilder {
     fps_limit: FpsLimit,
+    redraw_policy: RedrawPolicy,
+    clock: Box<dyn Clock>,
 }
 
 impl TimeBuilder {
     pub fn new() -> Self {
         Self {
             fps_limit: FpsLimit::unlimited(),
+            redraw_policy: RedrawPolicy::default(),
+            clock: Box::new(MonotonicClock::new()),
         }
     }
 }
@@ -94,7 +316,118 @@ impl TimeBuilder {
         self
     }
 
+    pub fn with_redraw_policy(mut self, redraw_policy: RedrawPolicy) -> Self {
+        self.redraw_policy = redraw_policy;
+        self
+    }
+
+    /// Set the clock, it is the [`MonotonicClock`] by default.
+    pub fn with_clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = Box::new(clock);
+        self
+    }
+
     pub fn build(self) -> Time {
-        Time::new(self.fps_limit)
+        let mut time = Time::new(self.fps_limit, self.clock);
+        time.set_redraw_policy(self.redraw_policy);
+        time
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::engine::clock::ManualClock;
+
+    use super::*;
+
+    fn time(fps_limit: FpsLimit) -> (Time, ManualClock) {
+        let clock = ManualClock::new();
+        let time = TimeBuilder::new()
+            .with_fps_limit(fps_limit)
+            .with_clock(clock.clone())
+            .build();
+        (time, clock)
+    }
+
+    fn assert_secs(actual: f32, expected: f32) {
+        assert!(
+            (actual - expected).abs() < 1e-4,
+            "expected {expected}s, found {actual}s"
+        );
+    }
+
+    #[test]
+    fn delta_is_time_between_updates() {
+        let (mut time, clock) = time(FpsLimit::unlimited());
+
+        clock.advance(Duration::from_millis(16));
+        time.update();
+        assert_secs(time.delta(), 0.016);
+
+        clock.advance(Duration::from_millis(40));
+        time.update();
+        assert_secs(time.delta(), 0.040);
+
+        time.update();
+        assert_secs(time.delta(), 0.0);
+    }
+
+    #[test]
+    fn elapsed_excludes_paused_duration() {
+        let (mut time, clock) = time(FpsLimit::unlimited());
+
+        clock.advance(Duration::from_secs(1));
+        assert_secs(time.elapsed(), 1.0);
+
+        time.pause();
+        clock.advance(Duration::from_secs(5));
+        time.resume();
+        assert_secs(time.elapsed(), 1.0);
+
+        clock.advance(Duration::from_millis(500));
+        time.update();
+        assert_secs(time.elapsed(), 1.5);
+        assert_secs(time.delta(), 1.5);
+    }
+
+    #[test]
+    fn frame_timeout_keeps_to_fps_limit() {
+        let (mut time, clock) = time(FpsLimit::new(10));
+        let frame = FpsLimit::new(10).as_secs_f32().expect("limited");
+
+        time.update();
+        assert_secs(time.frame_timeout().expect("timeout"), frame);
+
+        clock.advance(Duration::from_millis(40));
+        assert_secs(time.frame_timeout().expect("timeout"), frame - 0.040);
+
+        clock.advance(Duration::from_millis(60));
+        assert_eq!(time.frame_timeout(), None);
+    }
+
+    #[test]
+    fn unlimited_has_no_frame_timeout() {
+        let (mut time, _) = time(FpsLimit::unlimited());
+
+        time.update();
+        assert_eq!(time.frame_timeout(), None);
+    }
+
+    #[test]
+    fn simulation_delta_is_scaled_and_stepped() {
+        let (mut time, clock) = time(FpsLimit::unlimited());
+        time.set_time_scale(2.0);
+        time.pause_simulation();
+        time.step();
+
+        clock.advance(Duration::from_millis(10));
+        time.update();
+        assert_secs(time.take_simulation_scale(), 2.0);
+        assert_secs(time.simulation_delta(), 0.020);
+
+        clock.advance(Duration::from_millis(10));
+        time.update();
+        assert_secs(time.take_simulation_scale(), 0.0);
+        assert_secs(time.simulation_delta(), 0.0);
     }
 }
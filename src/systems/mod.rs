@@ -1,12 +1,18 @@
 mod args;
 mod error;
+mod events;
 pub mod handlers;
 mod pipeline;
+mod resources;
+mod scheduler;
 mod signal;
 mod utils;
 
-pub use args::{Args, FpsLimit};
+pub use args::{Args, FpsLimit, RedrawPolicy};
 pub use error::Error;
+pub use events::{Events, Subscriber};
 pub use pipeline::Pipeline;
+pub use resources::Resources;
+pub use scheduler::*;
 pub use signal::*;
 pub use utils::*;
@@ -1,119 +1,123 @@
-use std::sync::Arc;
-
-use winit::{dpi::LogicalSize, window::Window};
-
 use crate::{
     engine,
-    systems::{handlers, Args, Signal},
+    systems::{
+        handlers, Args, CameraPositionSignal, Error, Order, Query, Scheduler, SchedulerBuilder,
+        Signal,
+    },
 };
 
 /// Pipeline.
 pub struct Pipeline {
-    time: handlers::Time,
-    display: handlers::Display,
-    cursor_lock: handlers::CursorLock,
-    camera: handlers::Camera,
-    pyramid: handlers::Pyramid,
+    scheduler: Scheduler,
+}
+
+impl Pipeline {
+    /// The scheduler running the handlers.
+    pub fn scheduler(&self) -> &Scheduler {
+        &self.scheduler
+    }
 }
 
 impl engine::SystemPipeline for Pipeline {
     type Args = Args;
     type InSignal = Signal;
     type OutSignal = Signal;
+    type Query = Query;
+    type Error = Error;
 
-    async fn init(window: Arc<Window>, configs: Self::Args) -> Self {
+    async fn init(
+        target: engine::RenderTarget,
+        gpu_cache: engine::GpuCache,
+        configs: Self::Args,
+    ) -> Result<Self, Self::Error> {
         log::debug!("Initializing system pipeline");
 
-        let time = handlers::TimeBuilder::new()
-            .with_fps_limit(configs.fps_limit)
-            .build();
+        let gpu = gpu_cache.get_or_init(&target, &configs.adapter).await?;
         let display = handlers::DisplayBuilder::new()
-            .with_window(window.clone())
+            .with_gpu(gpu.clone())
+            .with_target(target.clone())
             .with_clear_color(configs.clear_color)
-            .build()
-            .await;
-        let cursor_lock = handlers::CursorLockBuilder::new()
-            .with_window(window.clone())
-            .with_should_lock_cursor(true)
-            .build();
-        let camera = handlers::CameraBuilder::new()
-            .with_device(display.device())
-            .with_aspect_ratio(display.aspect_ratio())
-            .build();
-        let pyramid = handlers::PyramidBuilder::new()
-            .with_device(display.device())
-            .with_surface_config(display.config())
-            .with_camera_bind_group_layout(camera.bind_group_layout())
-            .with_pyramid_transform(configs.pyramid_transform)
-            .with_model(configs.pyramid_model)
-            .build();
+            .with_clear_alpha(configs.clear_alpha)
+            .with_surface_preferences(configs.surface)
+            .with_depth(configs.depth)
+            .with_sample_count(configs.sample_count)
+            .build()?;
+
+        let mut scheduler = SchedulerBuilder::new()
+            .with_resource(gpu)
+            .with_handler::<handlers::Time>(Order::new())
+            .with_handler::<handlers::Camera>(
+                Order::new()
+                    .after::<handlers::Time>()
+                    .after::<handlers::CursorLock>(),
+            )
+            .with_handler::<handlers::Pyramid>(Order::new().after::<handlers::Camera>());
+        if target.window().is_some() {
+            scheduler = scheduler
+                .with_handler::<handlers::CursorLock>(Order::new().after::<handlers::Time>());
+        }
+        let scheduler = scheduler.build(&target, display, &configs)?;
 
         log::info!("System pipeline initialized");
 
-        Self {
-            time,
-            display,
-            cursor_lock,
-            camera,
-            pyramid,
-        }
+        Ok(Self { scheduler })
     }
 
     fn window_event(
         &mut self,
-        _: &mut engine::Items<Self::OutSignal>,
+        items: &mut engine::Items<Self::OutSignal>,
         event: &winit::event::WindowEvent,
     ) {
-        self.cursor_lock.window_event(event);
+        self.scheduler.window_event(items, event);
+    }
+
+    fn device_event(
+        &mut self,
+        items: &mut engine::Items<Self::OutSignal>,
+        event: &winit::event::DeviceEvent,
+    ) {
+        self.scheduler.device_event(items, event);
     }
 
     fn update(&mut self, items: &mut engine::Items<Self::OutSignal>) {
-        // Updates
-        self.time.update();
-        self.display.update(&items.input);
-        self.cursor_lock.update(&mut items.input);
-        self.pyramid.update(self.time.delta());
-
-        if self.cursor_lock.is_cursor_locked() {
-            self.camera.update(self.time.delta(), &items.input);
-        }
+        self.scheduler.frame(items);
+    }
 
-        // Signal
-        if let Some(tx) = items.tx.as_ref() {
-            self.pyramid.signal(tx);
-        }
+    fn fixed_update(&mut self, items: &mut engine::Items<Self::OutSignal>) {
+        self.scheduler.fixed_update(items);
+    }
+
+    fn pause(&mut self, items: &mut engine::Items<Self::OutSignal>) {
+        self.scheduler.pause(items);
+    }
 
-        // Render
-        self.display.render(|display, pass| {
-            self.camera
-                .render(display.queue(), display.aspect_ratio(), &items.input);
-            self.pyramid
-                .render(display.queue(), pass, self.camera.bind_group())
-        });
+    fn resume(&mut self, items: &mut engine::Items<Self::OutSignal>) {
+        self.scheduler.resume(items);
+    }
 
-        self.time.end_frame(items.window.clone());
+    fn surface_format(&self) -> Option<wgpu::TextureFormat> {
+        Some(self.scheduler.display().config().format)
     }
 
     fn in_signal(&mut self, items: &mut engine::Items<Self::OutSignal>, signal: Self::InSignal) {
-        match signal {
-            Signal::Resize(resize) => {
-                log::debug!(
-                    "Resize incoming signal: {} x {}",
-                    resize.width,
-                    resize.height
-                );
-                let _ = items
-                    .window
-                    .request_inner_size(LogicalSize::new(resize.width, resize.height));
-            }
-            Signal::PyramidTransformUpdate(update) => {
-                log::debug!("Pyramid transform incoming signal");
-                self.pyramid.set_transform(update.transform);
-            }
-            Signal::PyramidModelUpdate(update) => {
-                log::debug!("Pyramid model incoming signal");
-                self.pyramid.set_model(update.model);
-            }
+        self.scheduler.signal(items, &signal);
+    }
+
+    fn query(
+        &mut self,
+        _: &mut engine::Items<Self::OutSignal>,
+        query: Self::Query,
+    ) -> Result<Self::OutSignal, engine::signal::QueryError> {
+        match query {
+            Query::CameraPosition => self
+                .scheduler
+                .handler::<handlers::Camera>()
+                .map(|camera| {
+                    Signal::CameraPosition(CameraPositionSignal {
+                        position: camera.model().position,
+                    })
+                })
+                .ok_or(engine::signal::QueryError::Unsupported),
         }
     }
 }
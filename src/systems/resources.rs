@@ -0,0 +1,81 @@
+use std::{
+    any::{Any, TypeId},
+    collections::HashMap,
+};
+
+use crate::systems::Error;
+
+/// Resources shared between handlers, keyed by their type.
+///
+/// Handlers publish resources, e.g. the delta time or a bind group, and other
+/// handlers look them up without knowing who published them. Use a newtype to
+/// publish several resources of the same type.
+#[derive(Default)]
+pub struct Resources {
+    resources: HashMap<TypeId, Resource>,
+}
+
+struct Resource {
+    name: &'static str,
+    value: Box<dyn Any>,
+}
+
+impl Resources {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Insert a resource, returning the one it replaces.
+    pub fn insert<T: 'static>(&mut self, value: T) -> Option<T> {
+        self.resources
+            .insert(
+                TypeId::of::<T>(),
+                Resource {
+                    name: std::any::type_name::<T>(),
+                    value: Box::new(value),
+                },
+            )
+            .and_then(|resource| resource.value.downcast().ok())
+            .map(|value| *value)
+    }
+
+    pub fn remove<T: 'static>(&mut self) -> Option<T> {
+        self.resources
+            .remove(&TypeId::of::<T>())
+            .and_then(|resource| resource.value.downcast().ok())
+            .map(|value| *value)
+    }
+
+    pub fn contains<T: 'static>(&self) -> bool {
+        self.resources.contains_key(&TypeId::of::<T>())
+    }
+
+    pub fn get<T: 'static>(&self) -> Option<&T> {
+        self.resources
+            .get(&TypeId::of::<T>())
+            .and_then(|resource| resource.value.downcast_ref())
+    }
+
+    pub fn get_mut<T: 'static>(&mut self) -> Option<&mut T> {
+        self.resources
+            .get_mut(&TypeId::of::<T>())
+            .and_then(|resource| resource.value.downcast_mut())
+    }
+
+    /// Get a resource which is required, e.g. when initializing a handler.
+    pub fn require<T: 'static>(&self) -> Result<&T, Error> {
+        self.get()
+            .ok_or(Error::ResourceMissing(std::any::type_name::<T>()))
+    }
+
+    /// The type names of the resources, in no particular order.
+    pub fn names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.resources.values().map(|resource| resource.name)
+    }
+}
+
+impl std::fmt::Debug for Resources {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_set().entries(self.names()).finish()
+    }
+}
@@ -0,0 +1,871 @@
+use std::{
+    any::{Any, TypeId},
+    sync::{mpsc, Arc},
+    time::Duration,
+};
+
+use winit::event::{DeviceEvent, WindowEvent};
+use winit_input_helper::WinitInputHelper;
+
+use crate::{
+    engine::{self, Gpu, Items, NextFrame, RenderTarget},
+    systems::{
+        handlers::{CursorLockChanged, Display, SurfaceResized},
+        Args, Error, Events, Resources, Signal,
+    },
+};
+
+/// Handler run by the [`Scheduler`].
+///
+/// Every hook is called on all the handlers in the order of the scheduler, and
+/// the frame hooks are called stage by stage, see [`Stage`].
+#[allow(unused_variables)]
+pub trait Handler: AsAny {
+    /// Create the handler when the scheduler is built.
+    ///
+    /// The handlers ordered before it are already created, and their resources
+    /// are published in [`InitContext::resources`].
+    fn init(ctx: &mut InitContext) -> Result<Self, Error>
+    where
+        Self: Sized;
+
+    /// Called when there is a [`WindowEvent`].
+    fn window_event(&mut self, ctx: &mut Context, event: &WindowEvent) {}
+
+    /// Called when there is a [`DeviceEvent`].
+    fn device_event(&mut self, ctx: &mut Context, event: &DeviceEvent) {}
+
+    /// Called when there is an incoming [`Signal`].
+    fn signal(&mut self, ctx: &mut Context, signal: &Signal) {}
+
+    /// Called when the engine is paused.
+    fn pause(&mut self, ctx: &mut Context) {}
+
+    /// Called when the engine is resumed.
+    fn resume(&mut self, ctx: &mut Context) {}
+
+    /// Called when the GPU context is recovered after the device is lost, with
+    /// the new [`Gpu`] in the resources, to recreate the GPU resources from
+    /// the current state.
+    fn device_recovered(&mut self, ctx: &mut Context) {}
+
+    /// Called every tick in [`Stage::FixedUpdate`], with
+    /// [`Items::fixed_step`] set.
+    fn fixed_update(&mut self, ctx: &mut Context) {}
+
+    /// Called every frame in [`Stage::PreUpdate`].
+    fn pre_update(&mut self, ctx: &mut Context) {}
+
+    /// Called every frame in [`Stage::Update`].
+    fn update(&mut self, ctx: &mut Context) {}
+
+    /// Called every frame in [`Stage::Render`], with the render pass of the
+    /// [`Display`].
+    fn render(&mut self, ctx: &mut Context, pass: &mut wgpu::RenderPass) {}
+
+    /// Called every frame in [`Stage::PostRender`].
+    fn post_render(&mut self, ctx: &mut Context) {}
+}
+
+/// Conversion to [`Any`], so that handlers can be looked up by type.
+pub trait AsAny: Any {
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+impl<T: Any> AsAny for T {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// Stage run by the [`Scheduler`], in order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    /// Advance the simulation by a tick, zero or more times before a frame
+    /// when the engine has a [`crate::engine::FixedTimestep`].
+    FixedUpdate,
+    /// Prepare the frame, e.g. time and input.
+    PreUpdate,
+    /// Update the state.
+    Update,
+    /// Render to the [`Display`].
+    Render,
+    /// Finish the frame, e.g. outgoing signals.
+    PostRender,
+}
+
+impl Stage {
+    /// The stages of a frame.
+    pub const FRAME: [Stage; 4] = [
+        Stage::PreUpdate,
+        Stage::Update,
+        Stage::Render,
+        Stage::PostRender,
+    ];
+}
+
+/// Ordering constraints of a handler in the [`Scheduler`].
+///
+/// Constraints on handlers which are not registered are ignored.
+#[derive(Debug, Clone, Default)]
+pub struct Order {
+    after: Vec<TypeId>,
+    before: Vec<TypeId>,
+}
+
+impl Order {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Run after the handler `T`.
+    pub fn after<T: Handler>(mut self) -> Self {
+        self.after.push(TypeId::of::<T>());
+        self
+    }
+
+    /// Run before the handler `T`.
+    pub fn before<T: Handler>(mut self) -> Self {
+        self.before.push(TypeId::of::<T>());
+        self
+    }
+}
+
+/// Context of the handler hooks.
+pub struct Context<'a> {
+    /// The engine items.
+    pub items: &'a mut Items<Signal>,
+    /// The display rendered to.
+    pub display: &'a Display,
+    /// The resources shared between handlers.
+    pub resources: &'a mut Resources,
+    /// The event bus between handlers.
+    pub events: &'a mut Events,
+    stage: Option<Stage>,
+    handlers: &'a [HandlerEntry],
+}
+
+impl Context<'_> {
+    /// The stage being run, [`None`] outside of the frame hooks.
+    pub fn stage(&self) -> Option<Stage> {
+        self.stage
+    }
+
+    /// Look up another handler.
+    ///
+    /// The handler running the hook cannot look up itself.
+    pub fn handler<T: Handler>(&self) -> Option<&T> {
+        find_handler(self.handlers)
+    }
+
+    /// Request a redraw, e.g. when the state of the handler changed.
+    ///
+    /// This is only needed with [`crate::systems::RedrawPolicy::OnDemand`].
+    pub fn request_redraw(&mut self) {
+        self.resources.insert(RedrawRequested);
+    }
+
+    /// Whether a redraw is requested since the last frame.
+    pub fn is_redraw_requested(&self) -> bool {
+        self.resources.contains::<RedrawRequested>()
+    }
+}
+
+/// Resource marking that a redraw is requested, see [`Context::request_redraw`].
+#[derive(Debug)]
+struct RedrawRequested;
+
+/// Context of [`Handler::init`].
+pub struct InitContext<'a> {
+    pub target: &'a RenderTarget,
+    pub display: &'a Display,
+    pub args: &'a Args,
+    /// The resources shared between handlers.
+    pub resources: &'a mut Resources,
+    /// The event bus between handlers, e.g. to subscribe.
+    pub events: &'a mut Events,
+    handlers: &'a [HandlerEntry],
+}
+
+impl InitContext<'_> {
+    /// Look up a handler created before.
+    pub fn handler<T: Handler>(&self) -> Option<&T> {
+        find_handler(self.handlers)
+    }
+
+    /// Look up a handler created before, which is required.
+    pub fn require<T: Handler>(&self) -> Result<&T, Error> {
+        self.handler()
+            .ok_or(Error::HandlerMissing(std::any::type_name::<T>()))
+    }
+}
+
+/// Runs the registered handlers in stages.
+///
+/// It owns the [`Display`], which is resized at the start of
+/// [`Stage::PreUpdate`] and provides the render pass of [`Stage::Render`], and
+/// the [`Resources`] and [`Events`] shared between the handlers.
+///
+/// When the device is lost, frames are skipped until the GPU context is
+/// recovered, see [`Handler::device_recovered`].
+pub struct Scheduler {
+    display: Display,
+    resources: Resources,
+    events: Events,
+    handlers: Vec<HandlerEntry>,
+    recovery: Option<mpsc::Receiver<Result<Gpu, engine::Error>>>,
+    cursor_locked: bool,
+}
+
+struct HandlerEntry {
+    name: &'static str,
+    // Taken out while its hook is running
+    handler: Option<Box<dyn Handler>>,
+}
+
+impl Scheduler {
+    /// Interval of the frames checking the GPU context being recovered.
+    const RECOVERY_INTERVAL: Duration = Duration::from_millis(100);
+
+    pub fn display(&self) -> &Display {
+        &self.display
+    }
+
+    pub fn resources(&self) -> &Resources {
+        &self.resources
+    }
+
+    pub fn resources_mut(&mut self) -> &mut Resources {
+        &mut self.resources
+    }
+
+    pub fn events(&self) -> &Events {
+        &self.events
+    }
+
+    /// The names of the handlers, in order.
+    pub fn handler_names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.handlers.iter().map(|entry| entry.name)
+    }
+
+    pub fn handler<T: Handler>(&self) -> Option<&T> {
+        find_handler(&self.handlers)
+    }
+
+    pub fn handler_mut<T: Handler>(&mut self) -> Option<&mut T> {
+        self.handlers
+            .iter_mut()
+            .filter_map(|entry| entry.handler.as_deref_mut())
+            .find_map(|handler| handler.as_any_mut().downcast_mut())
+    }
+
+    pub fn window_event(&mut self, items: &mut Items<Signal>, event: &WindowEvent) {
+        run_handlers(
+            &mut self.handlers,
+            items,
+            &self.display,
+            &mut self.resources,
+            &mut self.events,
+            None,
+            |handler, ctx| handler.window_event(ctx, event),
+        );
+    }
+
+    pub fn device_event(&mut self, items: &mut Items<Signal>, event: &DeviceEvent) {
+        run_handlers(
+            &mut self.handlers,
+            items,
+            &self.display,
+            &mut self.resources,
+            &mut self.events,
+            None,
+            |handler, ctx| handler.device_event(ctx, event),
+        );
+    }
+
+    /// Pass the signal to the display, then to all the handlers.
+    pub fn signal(&mut self, items: &mut Items<Signal>, signal: &Signal) {
+        self.display.signal(signal);
+        run_handlers(
+            &mut self.handlers,
+            items,
+            &self.display,
+            &mut self.resources,
+            &mut self.events,
+            None,
+            |handler, ctx| handler.signal(ctx, signal),
+        );
+    }
+
+    pub fn pause(&mut self, items: &mut Items<Signal>) {
+        run_handlers(
+            &mut self.handlers,
+            items,
+            &self.display,
+            &mut self.resources,
+            &mut self.events,
+            None,
+            |handler, ctx| handler.pause(ctx),
+        );
+    }
+
+    pub fn resume(&mut self, items: &mut Items<Signal>) {
+        run_handlers(
+            &mut self.handlers,
+            items,
+            &self.display,
+            &mut self.resources,
+            &mut self.events,
+            None,
+            |handler, ctx| handler.resume(ctx),
+        );
+    }
+
+    /// Run a tick of the fixed timestep.
+    pub fn fixed_update(&mut self, items: &mut Items<Signal>) {
+        self.events.advance();
+        self.run_stage(items, Stage::FixedUpdate);
+    }
+
+    /// Run a frame, stage by stage.
+    ///
+    /// A redraw requested by [`Context::request_redraw`] is cleared after the
+    /// frame.
+    pub fn frame(&mut self, items: &mut Items<Signal>) {
+        if !self.recover_device(items) {
+            items.request_frame(NextFrame::After(Self::RECOVERY_INTERVAL));
+            return;
+        }
+
+        for stage in Stage::FRAME {
+            self.events.advance();
+            self.run_stage(items, stage);
+        }
+        self.events.end_frame();
+        self.resources.remove::<RedrawRequested>();
+    }
+
+    /// Recover the GPU context if the device is lost, then the display and the
+    /// handlers, returning whether the device can be used.
+    ///
+    /// Recovering is retried on the next frame if it fails.
+    fn recover_device(&mut self, items: &mut Items<Signal>) -> bool {
+        let rx = match &self.recovery {
+            Some(rx) => rx,
+            None if !self.display.gpu().is_lost() => return true,
+            None => {
+                log::warn!("GPU device lost, recovering");
+                let (tx, rx) = mpsc::channel();
+                let recover = self.display.recover_gpu();
+                engine::utils::spawn(async move {
+                    if tx.send(recover.await).is_err() {
+                        log::warn!("Scheduler dropped before GPU context recovered");
+                    }
+                });
+                self.recovery.insert(rx)
+            }
+        };
+
+        let gpu = match rx.try_recv() {
+            Ok(Ok(gpu)) => Arc::new(gpu),
+            Ok(Err(e)) => {
+                log::error!("GPU context failed to recover: {e}");
+                self.recovery = None;
+                return false;
+            }
+            Err(mpsc::TryRecvError::Empty) => return false,
+            Err(mpsc::TryRecvError::Disconnected) => {
+                self.recovery = None;
+                return false;
+            }
+        };
+        self.recovery = None;
+
+        if let Err(e) = self.display.set_gpu(gpu.clone()) {
+            log::error!("Display failed to recover: {e}");
+            gpu.set_lost();
+            return false;
+        }
+
+        items.gpu_cache.set(gpu.clone());
+        self.resources.insert(gpu);
+        run_handlers(
+            &mut self.handlers,
+            items,
+            &self.display,
+            &mut self.resources,
+            &mut self.events,
+            None,
+            |handler, ctx| handler.device_recovered(ctx),
+        );
+
+        log::info!("GPU context recovered");
+        true
+    }
+
+    /// Resize the display, emitting [`SurfaceResized`] if its size changed.
+    fn update_display(&mut self, input: &WinitInputHelper) {
+        let size = |display: &Display| (display.config().width, display.config().height);
+
+        let old_size = size(&self.display);
+        self.display.update(input);
+        let (width, height) = size(&self.display);
+
+        if old_size != (width, height) {
+            self.events.emit(SurfaceResized { width, height });
+        }
+    }
+
+    fn run_stage(&mut self, items: &mut Items<Signal>, stage: Stage) {
+        if stage == Stage::PreUpdate {
+            self.update_display(&items.input);
+        }
+
+        let handlers = &mut self.handlers;
+        let resources = &mut self.resources;
+        let events = &mut self.events;
+        match stage {
+            Stage::FixedUpdate => run_handlers(
+                handlers,
+                items,
+                &self.display,
+                resources,
+                events,
+                Some(stage),
+                |handler, ctx| handler.fixed_update(ctx),
+            ),
+            Stage::PreUpdate => {
+                run_handlers(
+                    handlers,
+                    items,
+                    &self.display,
+                    resources,
+                    events,
+                    Some(stage),
+                    |handler, ctx| handler.pre_update(ctx),
+                );
+
+                // Also set by the replay, when there is no window to lock
+                if self.cursor_locked != items.cursor_locked {
+                    self.cursor_locked = items.cursor_locked;
+                    events.emit(CursorLockChanged {
+                        locked: items.cursor_locked,
+                    });
+                }
+            }
+            Stage::Update => run_handlers(
+                handlers,
+                items,
+                &self.display,
+                resources,
+                events,
+                Some(stage),
+                |handler, ctx| handler.update(ctx),
+            ),
+            Stage::Render => {
+                let is_rendered = self.display.render(|display, pass| {
+                    run_handlers(
+                        handlers,
+                        items,
+                        display,
+                        resources,
+                        events,
+                        Some(stage),
+                        |handler, ctx| handler.render(ctx, pass),
+                    )
+                });
+
+                // Try again on the next frame
+                if !is_rendered {
+                    resources.insert(RedrawRequested);
+                }
+            }
+            Stage::PostRender => run_handlers(
+                handlers,
+                items,
+                &self.display,
+                resources,
+                events,
+                Some(stage),
+                |handler, ctx| handler.post_render(ctx),
+            ),
+        }
+    }
+}
+
+/// Run a hook on all the handlers in order.
+fn run_handlers(
+    handlers: &mut [HandlerEntry],
+    items: &mut Items<Signal>,
+    display: &Display,
+    resources: &mut Resources,
+    events: &mut Events,
+    stage: Option<Stage>,
+    mut hook: impl FnMut(&mut dyn Handler, &mut Context),
+) {
+    for i in 0..handlers.len() {
+        let Some(mut handler) = handlers[i].handler.take() else {
+            log::error!("Handler {} is already running", handlers[i].name);
+            continue;
+        };
+
+        hook(
+            handler.as_mut(),
+            &mut Context {
+                items,
+                display,
+                resources,
+                events,
+                stage,
+                handlers,
+            },
+        );
+
+        handlers[i].handler = Some(handler);
+    }
+}
+
+fn find_handler<T: Handler>(handlers: &[HandlerEntry]) -> Option<&T> {
+    handlers
+        .iter()
+        .filter_map(|entry| entry.handler.as_deref())
+        .find_map(|handler| handler.as_any().downcast_ref())
+}
+
+/// Builder of [`Scheduler`].
+pub struct SchedulerBuilder {
+    registrations: Vec<Registration>,
+    resources: Resources,
+    events: Events,
+}
+
+struct Registration {
+    name: &'static str,
+    type_id: TypeId,
+    order: Order,
+    init: fn(&mut InitContext) -> Result<Box<dyn Handler>, Error>,
+}
+
+impl SchedulerBuilder {
+    pub fn new() -> Self {
+        Self {
+            registrations: Vec::new(),
+            resources: Resources::new(),
+            events: Events::new(),
+        }
+    }
+
+    /// Register a handler with its ordering constraints.
+    ///
+    /// Handlers without constraints between them run in the order they are
+    /// registered.
+    pub fn with_handler<T: Handler>(mut self, order: Order) -> Self {
+        fn init<T: Handler>(ctx: &mut InitContext) -> Result<Box<dyn Handler>, Error> {
+            Ok(Box::new(T::init(ctx)?))
+        }
+
+        self.registrations.push(Registration {
+            name: std::any::type_name::<T>(),
+            type_id: TypeId::of::<T>(),
+            order,
+            init: init::<T>,
+        });
+        self
+    }
+
+    /// Publish a resource before the handlers are created, e.g. the GPU context.
+    pub fn with_resource<T: 'static>(mut self, resource: T) -> Self {
+        self.resources.insert(resource);
+        self
+    }
+
+    /// Sort the handlers, then create them in order.
+    pub fn build(
+        mut self,
+        target: &RenderTarget,
+        display: Display,
+        args: &Args,
+    ) -> Result<Scheduler, Error> {
+        let mut handlers = Vec::<HandlerEntry>::with_capacity(self.registrations.len());
+        let mut resources = std::mem::take(&mut self.resources);
+        let mut events = std::mem::take(&mut self.events);
+
+        for registration in self.sorted()? {
+            log::debug!("Initializing handler {}", registration.name);
+            let handler = (registration.init)(&mut InitContext {
+                target,
+                display: &display,
+                args,
+                resources: &mut resources,
+                events: &mut events,
+                handlers: &handlers,
+            })?;
+
+            handlers.push(HandlerEntry {
+                name: registration.name,
+                handler: Some(handler),
+            });
+        }
+
+        log::info!(
+            "Scheduler initialized: {:?}, resources: {resources:?}",
+            handlers.iter().map(|entry| entry.name).collect::<Vec<_>>()
+        );
+
+        Ok(Scheduler {
+            display,
+            resources,
+            events,
+            handlers,
+            recovery: None,
+            cursor_locked: false,
+        })
+    }
+
+    /// Sort the registrations by their ordering constraints, keeping the
+    /// registration order otherwise.
+    fn sorted(self) -> Result<Vec<Registration>, Error> {
+        let index_of = |type_id: &TypeId| {
+            self.registrations
+                .iter()
+                .position(|registration| registration.type_id == *type_id)
+        };
+
+        // `dependencies[i]` are the registrations that must run before `i`
+        let mut dependencies = vec![Vec::new(); self.registrations.len()];
+        for (i, registration) in self.registrations.iter().enumerate() {
+            for j in registration.order.after.iter().filter_map(index_of) {
+                dependencies[i].push(j);
+            }
+            for j in registration.order.before.iter().filter_map(index_of) {
+                dependencies[j].push(i);
+            }
+        }
+
+        let mut sorted = Vec::with_capacity(self.registrations.len());
+        let mut is_sorted = vec![false; self.registrations.len()];
+        while sorted.len() < self.registrations.len() {
+            let next = (0..self.registrations.len())
+                .find(|&i| !is_sorted[i] && dependencies[i].iter().all(|&j| is_sorted[j]));
+
+            match next {
+                Some(i) => {
+                    is_sorted[i] = true;
+                    sorted.push(i);
+                }
+                None => {
+                    return Err(Error::SchedulerCycle(
+                        (0..self.registrations.len())
+                            .filter(|&i| !is_sorted[i])
+                            .map(|i| self.registrations[i].name)
+                            .collect(),
+                    ))
+                }
+            }
+        }
+
+        let mut registrations = self.registrations.into_iter().map(Some).collect::<Vec<_>>();
+        Ok(sorted
+            .into_iter()
+            .filter_map(|i| registrations[i].take())
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use winit::dpi::PhysicalSize;
+
+    use super::*;
+    use crate::{
+        engine::{GpuCache, Keys},
+        systems::handlers::DisplayBuilder,
+    };
+
+    /// Resource of the hooks called, in order.
+    #[derive(Debug, Default)]
+    struct Calls(Vec<(Option<Stage>, &'static str)>);
+
+    fn record<T: Handler>(ctx: &mut Context) {
+        let stage = ctx.stage();
+        if let Some(calls) = ctx.resources.get_mut::<Calls>() {
+            calls.0.push((stage, std::any::type_name::<T>()));
+        }
+    }
+
+    macro_rules! test_handlers {
+        ($($name:ident),*) => {
+            $(
+                struct $name;
+
+                impl Handler for $name {
+                    fn init(_: &mut InitContext) -> Result<Self, Error> {
+                        Ok(Self)
+                    }
+
+                    fn fixed_update(&mut self, ctx: &mut Context) {
+                        record::<Self>(ctx);
+                    }
+
+                    fn pre_update(&mut self, ctx: &mut Context) {
+                        record::<Self>(ctx);
+                    }
+
+                    fn update(&mut self, ctx: &mut Context) {
+                        record::<Self>(ctx);
+                    }
+
+                    fn render(&mut self, ctx: &mut Context, _: &mut wgpu::RenderPass) {
+                        record::<Self>(ctx);
+                    }
+
+                    fn post_render(&mut self, ctx: &mut Context) {
+                        record::<Self>(ctx);
+                    }
+                }
+            )*
+        };
+    }
+
+    test_handlers!(A, B, C, D);
+
+    fn name<T: Handler>() -> &'static str {
+        std::any::type_name::<T>()
+    }
+
+    fn sorted_names(builder: SchedulerBuilder) -> Result<Vec<&'static str>, Error> {
+        Ok(builder
+            .sorted()?
+            .into_iter()
+            .map(|registration| registration.name)
+            .collect())
+    }
+
+    #[test]
+    fn after_and_before_constraints() {
+        let builder = SchedulerBuilder::new()
+            .with_handler::<A>(Order::new().after::<B>())
+            .with_handler::<B>(Order::new())
+            .with_handler::<C>(Order::new().before::<B>());
+
+        assert_eq!(
+            sorted_names(builder).unwrap(),
+            [name::<C>(), name::<B>(), name::<A>()]
+        );
+    }
+
+    #[test]
+    fn unconstrained_keep_registration_order() {
+        let builder = SchedulerBuilder::new()
+            .with_handler::<A>(Order::new())
+            .with_handler::<B>(Order::new())
+            .with_handler::<C>(Order::new());
+        assert_eq!(
+            sorted_names(builder).unwrap(),
+            [name::<A>(), name::<B>(), name::<C>()]
+        );
+
+        // Only `B` is moved, after `C`
+        let builder = SchedulerBuilder::new()
+            .with_handler::<A>(Order::new())
+            .with_handler::<B>(Order::new().after::<C>())
+            .with_handler::<C>(Order::new())
+            .with_handler::<D>(Order::new());
+        assert_eq!(
+            sorted_names(builder).unwrap(),
+            [name::<A>(), name::<C>(), name::<B>(), name::<D>()]
+        );
+    }
+
+    #[test]
+    fn unregistered_constraints_ignored() {
+        let builder = SchedulerBuilder::new()
+            .with_handler::<A>(Order::new().after::<D>())
+            .with_handler::<B>(Order::new().before::<D>())
+            .with_handler::<C>(Order::new().before::<A>());
+
+        assert_eq!(
+            sorted_names(builder).unwrap(),
+            [name::<B>(), name::<C>(), name::<A>()]
+        );
+    }
+
+    #[test]
+    fn cycle_fails() {
+        let builder = SchedulerBuilder::new()
+            .with_handler::<A>(Order::new().after::<B>())
+            .with_handler::<B>(Order::new().after::<A>())
+            .with_handler::<C>(Order::new());
+
+        match sorted_names(builder) {
+            Err(Error::SchedulerCycle(names)) => assert_eq!(names, [name::<A>(), name::<B>()]),
+            result => panic!("expected a cycle, got {result:?}"),
+        }
+    }
+
+    #[test]
+    fn cycle_through_before_fails() {
+        let builder = SchedulerBuilder::new()
+            .with_handler::<A>(Order::new().before::<B>())
+            .with_handler::<B>(Order::new().before::<A>());
+
+        assert!(matches!(
+            sorted_names(builder),
+            Err(Error::SchedulerCycle(..))
+        ));
+    }
+
+    #[test]
+    fn stages_run_in_order() {
+        let target = RenderTarget::Headless(PhysicalSize::new(4, 4));
+        let Some(gpu_cache) = GpuCache::headless_for_test(&target) else {
+            return;
+        };
+        let gpu = gpu_cache.get().expect("cached GPU context");
+        let display = DisplayBuilder::new()
+            .with_gpu(gpu)
+            .with_target(target.clone())
+            .build()
+            .unwrap();
+
+        let mut scheduler = SchedulerBuilder::new()
+            .with_resource(Calls::default())
+            .with_handler::<A>(Order::new())
+            .with_handler::<B>(Order::new().before::<A>())
+            .build(&target, display, &Args::default())
+            .unwrap();
+        let mut items = Items {
+            target,
+            gpu_cache,
+            input: WinitInputHelper::new(),
+            keys: Keys::new(),
+            cursor_locked: false,
+            is_replay: false,
+            tx: None,
+            frame_delta: None,
+            fixed_step: None,
+            next_frame: NextFrame::Idle,
+        };
+
+        scheduler.fixed_update(&mut items);
+        scheduler.frame(&mut items);
+
+        let calls = &scheduler.resources().get::<Calls>().unwrap().0;
+        let expected = [
+            Stage::FixedUpdate,
+            Stage::PreUpdate,
+            Stage::Update,
+            Stage::Render,
+            Stage::PostRender,
+        ]
+        .into_iter()
+        .flat_map(|stage| [(Some(stage), name::<B>()), (Some(stage), name::<A>())])
+        .collect::<Vec<_>>();
+        assert_eq!(calls, &expected);
+    }
+}
@@ -1,3 +1,6 @@
+use glam::*;
+use serde::{Deserialize, Serialize};
+
 use crate::{
     engine::{self, signal::QueueBehavior},
     systems::{handlers::PyramidModel, Pipeline},
@@ -8,8 +11,8 @@ use super::handlers::PyramidTransform;
 /// Type alias for the [`engine::InSignal`] of [`crate::systems::Pipeline`].
 pub type EngineInSignal = engine::InSignal<Pipeline>;
 
-/// Type alias for the [`engine::SystemPipeline::OutSignal`] of [`crate::systems::Pipeline`].
-pub type EngineOutSignal = <Pipeline as engine::SystemPipeline>::OutSignal;
+/// Type alias for the [`engine::OutSignal`] of [`crate::systems::Pipeline`].
+pub type EngineOutSignal = engine::OutSignal<<Pipeline as engine::SystemPipeline>::OutSignal>;
 
 macro_rules! signals {
     (
@@ -24,7 +27,7 @@ macro_rules! signals {
             /// Incoming and outgoing signal of [`Pipeline`].
             ///
             /// The same type is used for both incoming and outgoing just for simplicity.
-            #[derive(strum::EnumIs)]
+            #[derive(strum::EnumIs, Serialize, Deserialize)]
             pub enum Signal {
                 $($name([< $name Signal >]),)*
             }
@@ -39,6 +42,7 @@ macro_rules! signals {
 
             $(
                 /// Signal for [`Signal`].
+                #[derive(Serialize, Deserialize)]
                 pub struct [< $name Signal >] {
                     $(pub $field: $type,)*
                 }
@@ -52,7 +56,7 @@ macro_rules! signals {
                     }
 
                     pub fn out_signal($($field: $type),*) -> EngineOutSignal {
-                        Signal::$name(Self { $($field),* })
+                        EngineOutSignal::Custom(Signal::$name(Self { $($field),* }))
                     }
                 }
             )*
@@ -61,19 +65,75 @@ macro_rules! signals {
 }
 
 signals! {
-    #[queue = QueueBehavior::Replace(|a, _| a.is_resize())]
+    #[queue = QueueBehavior::replace(|a, _| a.is_resize())]
     Resize {
         width: f64,
         height: f64,
     }
 
-    #[queue = QueueBehavior::Ignored]
+    // Coalesced instead of ignored while the engine is stopped, so the last
+    // update sent while stopped is applied once the engine starts.
+    #[queue = QueueBehavior::coalesce(Signal::coalesce_replace)]
     PyramidTransformUpdate {
         transform: PyramidTransform,
     }
 
-    #[queue = QueueBehavior::Ignored]
+    #[queue = QueueBehavior::coalesce(Signal::coalesce_replace)]
     PyramidModelUpdate {
         model: PyramidModel,
     }
+
+    #[queue = QueueBehavior::ignored()]
+    CameraPosition {
+        position: Vec3,
+    }
+
+    #[queue = QueueBehavior::coalesce(Signal::coalesce_replace)]
+    TimeScale {
+        scale: f32,
+    }
+
+    #[queue = QueueBehavior::coalesce(Signal::coalesce_replace)]
+    SimulationPause {
+        paused: bool,
+    }
+
+    #[queue = QueueBehavior::queued()]
+    SimulationStep {}
+
+    #[queue = QueueBehavior::coalesce(Signal::coalesce_replace)]
+    PresentMode {
+        present_mode: wgpu::PresentMode,
+    }
+
+    #[queue = QueueBehavior::coalesce(Signal::coalesce_replace)]
+    SampleCount {
+        sample_count: u32,
+    }
+}
+
+impl Signal {
+    /// Replace a queued signal of the same kind with the signal.
+    ///
+    /// This is for [`engine::signal::QueueMode::Coalesce`], so a flood of updates
+    /// collapses to one per frame. The fields are not merged, the queued signal
+    /// is dropped as a whole, so it is only for signals carrying the full state
+    /// of their kind, e.g. [`PyramidTransformUpdateSignal`] sends the whole
+    /// [`PyramidTransform`].
+    pub fn coalesce_replace(queued: &mut Signal, signal: Signal) -> Option<Signal> {
+        match std::mem::discriminant(queued) == std::mem::discriminant(&signal) {
+            true => {
+                *queued = signal;
+                None
+            }
+            false => Some(signal),
+        }
+    }
+}
+
+/// Query of [`Pipeline`], replied with a [`Signal`].
+#[derive(Debug, Clone)]
+pub enum Query {
+    /// Replied with [`Signal::CameraPosition`].
+    CameraPosition,
 }
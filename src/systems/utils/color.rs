@@ -2,6 +2,7 @@ use std::ops::Deref;
 
 use glam::*;
 use ordered_float::OrderedFloat;
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
 macro_rules! rgb_basic_colors {
@@ -13,7 +14,9 @@ macro_rules! rgb_basic_colors {
 }
 
 #[repr(C)]
-#[derive(Debug, Clone, Copy, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+#[derive(
+    Debug, Clone, Copy, PartialEq, bytemuck::Pod, bytemuck::Zeroable, Serialize, Deserialize,
+)]
 pub struct RgbColor(Vec3);
 
 impl RgbColor {
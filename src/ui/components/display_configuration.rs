@@ -0,0 +1,36 @@
+use leptos::*;
+
+use crate::ui::components::engine::EngineController;
+
+/// Sample counts of the select, WebGPU only guarantees 1 and 4.
+const SAMPLE_COUNTS: &[(u32, &str)] = &[(1, "Off"), (4, "4x")];
+
+#[component]
+pub fn DisplayConfiguration(#[prop(into)] controller: EngineController) -> impl IntoView {
+    view! {
+        <div>
+            <h4 style="margin-top: 0; margin-bottom: 16px;">"Display"</h4>
+            <div style="display: flex; gap: 16px; justify-content: space-between;">
+                <label for="sample-count">"Anti-aliasing (MSAA)"</label>
+                <select
+                    id="sample-count"
+                    prop:value=move || controller.sample_count().get().to_string()
+                    on:change=move |event| {
+                        match event_target_value(&event).parse::<u32>() {
+                            Ok(sample_count) => controller.set_sample_count(sample_count),
+                            Err(e) => log::warn!("Invalid sample count: {e}"),
+                        }
+                    }
+                >
+                    {SAMPLE_COUNTS
+                        .iter()
+                        .map(|(sample_count, label)| view! {
+                            <option value=sample_count.to_string()>{*label}</option>
+                        })
+                        .collect_view()
+                    }
+                </select>
+            </div>
+        </div>
+    }
+}
@@ -2,7 +2,7 @@ use leptos::*;
 use leptos_use::{use_element_bounding, use_interval_fn};
 
 use crate::{
-    systems,
+    engine, systems,
     ui::components::{
         engine_canvas::{EngineRx, EngineTx},
         EngineCanvas,
@@ -13,6 +13,8 @@ use crate::{
 #[component]
 pub fn Engine(
     #[prop(optional, into)] controller: Option<EngineController>,
+    #[prop(optional)] redraw_policy: systems::RedrawPolicy,
+    #[prop(optional)] surface: systems::handlers::SurfacePreferences,
     #[prop(default = "".to_string(), into)] style: String,
 ) -> impl IntoView {
     let container_node = create_node_ref::<html::Div>();
@@ -22,13 +24,14 @@ pub fn Engine(
 
     // Add handler for engine's signal.
     controller.add_rx_handler(move |signal| match signal {
-        systems::Signal::PyramidTransformUpdate(signal) => {
+        engine::OutSignal::Lifecycle(signal) => controller.lifecycle(signal),
+        engine::OutSignal::Custom(systems::Signal::PyramidTransformUpdate(signal)) => {
             controller.pyramid_transform.set(signal.transform);
         }
-        systems::Signal::PyramidModelUpdate(signal) => {
+        engine::OutSignal::Custom(systems::Signal::PyramidModelUpdate(signal)) => {
             controller.pyramid_model.set(signal.model);
         }
-        _ => log::warn!("Unhandled signal: {signal:?}"),
+        signal => log::warn!("Unhandled signal: {signal:?}"),
     });
 
     // Keep the engine same size as the container.
@@ -77,6 +80,9 @@ pub fn Engine(
                     system_pipeline_args=move || systems::Args {
                         pyramid_transform: controller.pyramid_transform().get(),
                         pyramid_model: controller.pyramid_model().get(),
+                        redraw_policy,
+                        surface,
+                        sample_count: controller.sample_count().get(),
                         ..Default::default()
                     }
                     tx=controller.tx().split()
@@ -91,10 +97,18 @@ pub fn Engine(
 #[derive(Debug, Clone, Copy)]
 pub struct EngineController {
     running: RwSignal<bool>,
+    status: RwSignal<EngineStatus>,
+    occluded: RwSignal<bool>,
+    info: RwSignal<Option<engine::signal::RenderInfo>>,
+    error: RwSignal<Option<String>>,
+    queries: StoredValue<engine::query::Queries<systems::Signal>>,
     tx: RwSignal<EngineTx>,
     rx: RwSignal<EngineRx>,
     pyramid_transform: RwSignal<systems::handlers::PyramidTransform>,
     pyramid_model: RwSignal<systems::handlers::PyramidModel>,
+    time_scale: RwSignal<f32>,
+    simulation_paused: RwSignal<bool>,
+    sample_count: RwSignal<u32>,
 }
 
 impl EngineController {
@@ -102,6 +116,60 @@ impl EngineController {
         self.running
     }
 
+    /// The status of the engine, as reported by its lifecycle signals.
+    pub fn status(&self) -> RwSignal<EngineStatus> {
+        self.status
+    }
+
+    /// Whether the canvas is hidden, e.g. scrolled off-screen or in a
+    /// background tab, so the engine does not render.
+    pub fn occluded(&self) -> RwSignal<bool> {
+        self.occluded
+    }
+
+    pub fn paused(&self) -> Signal<bool> {
+        let status = self.status;
+        Signal::derive(move || status.get().is_paused())
+    }
+
+    /// What the engine renders with, once it is initialized.
+    pub fn info(&self) -> RwSignal<Option<engine::signal::RenderInfo>> {
+        self.info
+    }
+
+    /// The error the engine last failed with, if any.
+    pub fn error(&self) -> RwSignal<Option<String>> {
+        self.error
+    }
+
+    /// Pause or resume the engine, keeping the system pipeline alive.
+    pub fn set_paused(&self, paused: bool) {
+        self.tx().with(|tx| match tx {
+            Some(tx) => {
+                tx.send(match paused {
+                    true => engine::InSignal::Pause,
+                    false => engine::InSignal::Resume,
+                })
+                .unwrap();
+            }
+            None => log::debug!("Engine has not started, skipping pause"),
+        });
+    }
+
+    /// Query the engine and wait for the reply.
+    pub async fn query(
+        &self,
+        query: systems::Query,
+    ) -> Result<systems::Signal, engine::signal::QueryError> {
+        let Some(tx) = self.tx().get_untracked() else {
+            return Err(engine::signal::QueryError::Stopped);
+        };
+
+        self.queries
+            .with_value(|queries| queries.send(&tx, query, Some(Self::QUERY_TIMEOUT)))
+            .await
+    }
+
     pub fn tx(&self) -> RwSignal<EngineTx> {
         self.tx
     }
@@ -129,19 +197,117 @@ impl EngineController {
     pub fn pyramid_model(&self) -> RwSignal<systems::handlers::PyramidModel> {
         self.pyramid_model
     }
+
+    pub fn time_scale(&self) -> RwSignal<f32> {
+        self.time_scale
+    }
+
+    /// Set the factor of the simulation time.
+    pub fn set_time_scale(&self, scale: f32) {
+        self.time_scale.set(scale);
+        self.tx().with(|tx| match tx {
+            Some(tx) => {
+                tx.send(systems::TimeScaleSignal::in_signal(scale)).unwrap();
+            }
+            None => log::debug!("Engine has not started, skipping signal time scale"),
+        });
+    }
+
+    pub fn simulation_paused(&self) -> RwSignal<bool> {
+        self.simulation_paused
+    }
+
+    /// Pause or resume the simulation time, the engine keeps rendering.
+    pub fn set_simulation_paused(&self, paused: bool) {
+        self.simulation_paused.set(paused);
+        self.tx().with(|tx| match tx {
+            Some(tx) => {
+                tx.send(systems::SimulationPauseSignal::in_signal(paused))
+                    .unwrap();
+            }
+            None => log::debug!("Engine has not started, skipping signal simulation pause"),
+        });
+    }
+
+    /// Step the simulation by one frame while it is paused.
+    pub fn step_simulation(&self) {
+        self.tx().with(|tx| match tx {
+            Some(tx) => {
+                tx.send(systems::SimulationStepSignal::in_signal()).unwrap();
+            }
+            None => log::debug!("Engine has not started, skipping signal simulation step"),
+        });
+    }
+
+    pub fn sample_count(&self) -> RwSignal<u32> {
+        self.sample_count
+    }
+
+    /// Set the number of samples per pixel, kept when the engine restarts.
+    pub fn set_sample_count(&self, sample_count: u32) {
+        self.sample_count.set(sample_count);
+        self.tx().with(|tx| match tx {
+            Some(tx) => {
+                tx.send(systems::SampleCountSignal::in_signal(sample_count))
+                    .unwrap();
+            }
+            None => log::debug!("Engine has not started, skipping signal sample count"),
+        });
+    }
 }
 
 impl EngineController {
-    pub fn add_rx_handler(&self, handler: impl Fn(systems::Signal) + Clone + 'static) {
+    /// Timeout of [`EngineController::query`].
+    const QUERY_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(1);
+
+    fn lifecycle(&self, signal: engine::signal::LifecycleSignal) {
+        use engine::signal::LifecycleSignal;
+
+        log::debug!("Engine lifecycle signal: {signal:?}");
+        match signal {
+            LifecycleSignal::Initializing => {
+                self.status.set(EngineStatus::Initializing);
+                self.occluded.set(false);
+            }
+            LifecycleSignal::Initialized(info) | LifecycleSignal::Restarted(info) => {
+                self.info.set(Some(info));
+                self.status.set(EngineStatus::Running);
+
+                // The time controls start over with the system pipeline
+                self.time_scale.set(1.0);
+                self.simulation_paused.set(false);
+            }
+            LifecycleSignal::Paused => self.status.set(EngineStatus::Paused),
+            LifecycleSignal::Resumed => self.status.set(EngineStatus::Running),
+            LifecycleSignal::Occluded => self.occluded.set(true),
+            LifecycleSignal::Visible => self.occluded.set(false),
+            LifecycleSignal::Stopped => self.status.set(EngineStatus::Stopped),
+            LifecycleSignal::Failed { error } => {
+                log::error!("Engine failed: {error}");
+                self.error.set(Some(error));
+                self.status.set(EngineStatus::Stopped);
+                self.running.set(false);
+            }
+        }
+    }
+
+    pub fn add_rx_handler(&self, handler: impl Fn(systems::EngineOutSignal) + Clone + 'static) {
         let rx = self.rx;
+        let queries = self.queries;
         use_interval_fn(
             move || {
-                rx.with(|rx: &EngineRx| {
-                    if let Some(rx) = rx {
-                        for signal in rx.try_iter() {
-                            handler(signal);
+                queries.with_value(|queries| {
+                    rx.with(|rx: &EngineRx| {
+                        if let Some(rx) = rx {
+                            for signal in rx.try_iter() {
+                                // Replies to queries are not passed to the handler
+                                if let Some(signal) = queries.handle(signal) {
+                                    handler(signal);
+                                }
+                            }
                         }
-                    }
+                    });
+                    queries.expire();
                 })
             },
             (systems::Args::default()
@@ -157,17 +323,42 @@ impl EngineController {
 impl Default for EngineController {
     fn default() -> Self {
         let running = create_rw_signal(false);
+        let status = create_rw_signal(EngineStatus::Stopped);
+        let occluded = create_rw_signal(false);
+        let info = create_rw_signal(None);
+        let error = create_rw_signal(None);
+        let queries = store_value(engine::query::Queries::new());
         let tx = create_rw_signal(None);
         let rx = create_rw_signal(None);
         let pyramid_transform = create_rw_signal(systems::handlers::PyramidTransform::default());
         let pyramid_model = create_rw_signal(systems::handlers::PyramidModel::default());
+        let time_scale = create_rw_signal(1.0);
+        let simulation_paused = create_rw_signal(false);
+        let sample_count = create_rw_signal(systems::Args::default().sample_count);
 
         Self {
             running,
+            status,
+            occluded,
+            info,
+            error,
+            queries,
             tx,
             rx,
             pyramid_transform,
             pyramid_model,
+            time_scale,
+            simulation_paused,
+            sample_count,
         }
     }
 }
+
+/// Status of the engine in [`EngineController`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, strum::EnumIs)]
+pub enum EngineStatus {
+    Stopped,
+    Initializing,
+    Running,
+    Paused,
+}
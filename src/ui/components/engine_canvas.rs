@@ -65,7 +65,7 @@ pub fn EngineCanvas(
             None => spawn_local(async move {
                 log::debug!("Starting engine canvas");
 
-                let (new_tx, rx) = mpsc::channel();
+                let (new_tx, rx) = engine::signal::channel();
                 set_tx.set(Some(new_tx));
 
                 let (tx, new_rx) = mpsc::channel();
@@ -88,7 +88,7 @@ pub fn EngineCanvas(
 }
 
 /// Engine incoming signal sender.
-pub type EngineTx = Option<mpsc::Sender<systems::EngineInSignal>>;
+pub type EngineTx = Option<engine::signal::InSignalSender<systems::Pipeline>>;
 
 /// Engine outgoing signal receiver.
 pub type EngineRx = Option<mpsc::Receiver<systems::EngineOutSignal>>;
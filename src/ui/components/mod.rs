@@ -1,11 +1,15 @@
+pub mod display_configuration;
 pub mod engine;
 pub mod engine_canvas;
 pub mod footer;
 pub mod pyramid_transform_configuration;
 pub mod side_panel;
+pub mod time_controls;
 
+pub use display_configuration::DisplayConfiguration;
 pub use engine::Engine;
 pub use engine_canvas::EngineCanvas;
 pub use footer::Footer;
 pub use pyramid_transform_configuration::PyramidTransformConfiguration;
 pub use side_panel::SidePanel;
+pub use time_controls::TimeControls;
@@ -1,13 +1,22 @@
 use leptos::*;
 
-use crate::ui::components::{engine::EngineController, PyramidTransformConfiguration};
+use crate::{
+    systems,
+    ui::components::{
+        engine::{EngineController, EngineStatus},
+        DisplayConfiguration, PyramidTransformConfiguration, TimeControls,
+    },
+};
 
 const INSTRUCTIONS: &[&str] = &[
     "Click the button to start or stop the engine.",
+    "Pause the engine to stop rendering, resuming is instant.",
+    "Query the camera to ask the engine for its current position.",
     "Click on the canvas to focus and lock the cursor.",
     "Move the mouse to look around when the cursor is locked.",
     "Use the W, A, S, D, Space, Shift keys to move around when the cursor is locked.",
     "Change the configurations to see the changes in real-time.",
+    "Scale, pause or step the simulation time with the time controls, rendering continues.",
     "Press the Escape key or the Tab key to unlock the cursor.",
 ];
 
@@ -16,6 +25,8 @@ pub fn SidePanel(
     #[prop(into)] controller: EngineController,
     #[prop(default = "".to_string(), into)] style: String,
 ) -> impl IntoView {
+    let (camera_position, set_camera_position) = create_signal(None::<String>);
+
     view! {
         <div style=format!("\
             display: flex; \
@@ -25,8 +36,17 @@ pub fn SidePanel(
             {style}
         ")>
             <h2>"wgpu + Leptos"</h2>
-            <div style="display: flex;">
-                <button on:click=move |_| controller.running().set(!controller.running().get())>
+            <div style="display: flex; gap: 8px;">
+                <button
+                    disabled=move || controller.status().get().is_initializing()
+                    on:click=move |_| {
+                        if !controller.running().get() {
+                            controller.status().set(EngineStatus::Initializing);
+                        }
+                        controller.error().set(None);
+                        controller.running().set(!controller.running().get());
+                    }
+                >
                     <Show
                         when=move || controller.running().get()
                         fallback=|| "Start Engine"
@@ -34,11 +54,92 @@ pub fn SidePanel(
                         "Stop Engine"
                     </Show>
                 </button>
+                <Show when=move || controller.running().get()>
+                    <button
+                        disabled=move || controller.status().get().is_initializing()
+                        on:click=move |_| controller.set_paused(!controller.paused().get())
+                    >
+                        <Show
+                            when=move || controller.paused().get()
+                            fallback=|| "Pause Engine"
+                        >
+                            "Resume Engine"
+                        </Show>
+                    </button>
+                    <button
+                        disabled=move || controller.status().get().is_initializing()
+                        on:click=move |_| spawn_local(async move {
+                            let text = match controller.query(systems::Query::CameraPosition).await {
+                                Ok(systems::Signal::CameraPosition(signal)) => {
+                                    format!("Camera position: {:.2}", signal.position)
+                                }
+                                Ok(signal) => format!("Unexpected reply: {signal:?}"),
+                                Err(e) => format!("Camera position query failed: {e}"),
+                            };
+                            set_camera_position.set(Some(text));
+                        })
+                    >
+                        "Query Camera"
+                    </button>
+                </Show>
             </div>
+            {move || camera_position.get().map(|text| view! {
+                <div style="margin-top: 8px;">{text}</div>
+            })}
+            <Show when=move || controller.error().get().is_some()>
+                <div style="color: red; margin-top: 8px;">
+                    {move || format!(
+                        "Engine error: {err}",
+                        err = controller.error().get().unwrap_or_default(),
+                    )}
+                </div>
+            </Show>
+            <div style="margin-top: 8px;">
+                {move || format!(
+                    "Status: {:?}{hidden}",
+                    controller.status().get(),
+                    hidden = match controller.occluded().get() {
+                        true => " (hidden, not rendering)",
+                        false => "",
+                    },
+                )}
+            </div>
+            {move || controller.info().get().map(|info| view! {
+                <div style="margin-top: 4px; font-size: small;">
+                    {format!(
+                        "{adapter}, {format}, {width} x {height}",
+                        adapter = info.adapter.as_ref().map_or(
+                            "Unknown adapter".to_string(),
+                            |adapter| format!("{} ({:?})", adapter.name, adapter.backend),
+                        ),
+                        format = info.surface.format.map_or(
+                            "unknown format".to_string(),
+                            |format| format!("{format:?}"),
+                        ),
+                        width = info.surface.size.width,
+                        height = info.surface.size.height,
+                    )}
+                </div>
+                {info.limits.map(|limits| view! {
+                    <div style="margin-top: 4px; font-size: small;">
+                        {format!(
+                            "Max texture {texture}, max bind groups {bind_groups}, \
+                            max storage buffers {storage_buffers}",
+                            texture = limits.max_texture_dimension_2d,
+                            bind_groups = limits.max_bind_groups,
+                            storage_buffers = limits.max_storage_buffers_per_shader_stage,
+                        )}
+                    </div>
+                })}
+            })}
             <div style="margin-bottom: 16px;" />
             <h3 style="margin-top: 0;">"Configurations"</h3>
             <PyramidTransformConfiguration controller=controller />
             <div style="margin-bottom: 16px;" />
+            <TimeControls controller=controller />
+            <div style="margin-bottom: 16px;" />
+            <DisplayConfiguration controller=controller />
+            <div style="margin-bottom: 16px;" />
             <h3 style="margin-top: 0;">"Instructions"</h3>
             <ul style="margin-top: 0;">
                 {INSTRUCTIONS
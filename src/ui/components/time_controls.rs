@@ -0,0 +1,61 @@
+use leptos::*;
+
+use crate::ui::components::engine::EngineController;
+
+/// Maximum time scale of the slider.
+const MAX_TIME_SCALE: f32 = 4.0;
+
+#[component]
+pub fn TimeControls(#[prop(into)] controller: EngineController) -> impl IntoView {
+    view! {
+        <div>
+            <h4 style="margin-top: 0; margin-bottom: 16px;">"Time"</h4>
+            <div style="display: flex; flex-direction: column; gap: 8px;">
+                <label for="time-scale">
+                    {move || format!("Time Scale: {:.2}", controller.time_scale().get())}
+                </label>
+                <div style="display: flex; gap: 16px; justify-content: space-between;">
+                    <input
+                        id="time-scale"
+                        type="range"
+                        min=0.0
+                        max=MAX_TIME_SCALE
+                        step=0.05
+                        prop:value=move || controller.time_scale().get()
+                        on:input=move |event| {
+                            match event_target_value(&event).parse::<f32>() {
+                                Ok(scale) => {
+                                    controller.set_time_scale(scale.clamp(0.0, MAX_TIME_SCALE))
+                                }
+                                Err(e) => log::warn!("Invalid time scale: {e}"),
+                            }
+                        }
+                    />
+                    <button on:click=move |_| controller.set_time_scale(1.0)>
+                        "Reset"
+                    </button>
+                </div>
+                <div style="display: flex; gap: 8px;">
+                    <button
+                        on:click=move |_| {
+                            controller.set_simulation_paused(!controller.simulation_paused().get())
+                        }
+                    >
+                        <Show
+                            when=move || controller.simulation_paused().get()
+                            fallback=|| "Pause Simulation"
+                        >
+                            "Resume Simulation"
+                        </Show>
+                    </button>
+                    <button
+                        disabled=move || !controller.simulation_paused().get()
+                        on:click=move |_| controller.step_simulation()
+                    >
+                        "Step"
+                    </button>
+                </div>
+            </div>
+        </div>
+    }
+}